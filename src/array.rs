@@ -0,0 +1,387 @@
+//! The Toml Array extensions
+
+use std::cmp::Ordering;
+
+use toml::Value;
+
+use crate::error::{Error, Result};
+use crate::read::TomlValueReadExt;
+use crate::util::name_of_val;
+
+/// Extension trait for modifying arrays in a toml::Value document
+///
+/// The array is resolved by query like with `TomlValueReadExt::read`. All functions fail with
+/// `Error::NotAvailable` if there is no value at the query, and with `Error::NotAnArray` if the
+/// value at the query is not an array.
+pub trait TomlValueArrayExt {
+    /// Extension function for getting an array from the current toml::Value document mutably
+    /// using a custom seperator
+    fn array_mut_with_seperator(&mut self, query: &str, sep: char) -> Result<&mut Vec<Value>>;
+
+    /// Extension function for getting an array from the current toml::Value document mutably
+    ///
+    /// See documentation of `TomlValueArrayExt::array_mut_with_seperator`
+    fn array_mut(&mut self, query: &str) -> Result<&mut Vec<Value>> {
+        self.array_mut_with_seperator(query, '.')
+    }
+
+    /// Append a value to the array using a custom seperator
+    fn push_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<()> {
+        self.array_mut_with_seperator(query, sep)
+            .map(|a| a.push(value))
+    }
+
+    /// Append a value to the array
+    ///
+    /// See documentation of `TomlValueArrayExt::push_with_seperator`
+    fn push(&mut self, query: &str, value: Value) -> Result<()> {
+        self.push_with_seperator(query, '.', value)
+    }
+
+    /// Remove the last value from the array using a custom seperator
+    ///
+    /// Returns `Ok(None)` if the array is empty.
+    fn pop_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        self.array_mut_with_seperator(query, sep).map(|a| a.pop())
+    }
+
+    /// Remove the last value from the array
+    ///
+    /// See documentation of `TomlValueArrayExt::pop_with_seperator`
+    fn pop(&mut self, query: &str) -> Result<Option<Value>> {
+        self.pop_with_seperator(query, '.')
+    }
+
+    /// Insert a value at `idx` in the array using a custom seperator, shifting all values after
+    /// it
+    ///
+    /// Fails with `Error::IndexOutOfBounds` if `idx` is greater than the length of the array.
+    fn insert_at_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        idx: usize,
+        value: Value,
+    ) -> Result<()> {
+        let array = self.array_mut_with_seperator(query, sep)?;
+        if idx > array.len() {
+            return Err(Error::IndexOutOfBounds(idx, array.len()));
+        }
+        array.insert(idx, value);
+        Ok(())
+    }
+
+    /// Insert a value at `idx` in the array, shifting all values after it
+    ///
+    /// See documentation of `TomlValueArrayExt::insert_at_with_seperator`
+    fn insert_at(&mut self, query: &str, idx: usize, value: Value) -> Result<()> {
+        self.insert_at_with_seperator(query, '.', idx, value)
+    }
+
+    /// Remove the value at `idx` from the array using a custom seperator, shifting all values
+    /// after it
+    ///
+    /// Fails with `Error::IndexOutOfBounds` if there is no value at `idx`.
+    fn remove_at_with_seperator(&mut self, query: &str, sep: char, idx: usize) -> Result<Value> {
+        let array = self.array_mut_with_seperator(query, sep)?;
+        if idx >= array.len() {
+            return Err(Error::IndexOutOfBounds(idx, array.len()));
+        }
+        Ok(array.remove(idx))
+    }
+
+    /// Remove the value at `idx` from the array, shifting all values after it
+    ///
+    /// See documentation of `TomlValueArrayExt::remove_at_with_seperator`
+    fn remove_at(&mut self, query: &str, idx: usize) -> Result<Value> {
+        self.remove_at_with_seperator(query, '.', idx)
+    }
+
+    /// Keep only the values of the array for which `f` returns `true`, using a custom seperator
+    fn retain_with_seperator<F>(&mut self, query: &str, sep: char, f: F) -> Result<()>
+    where
+        F: FnMut(&Value) -> bool,
+    {
+        self.array_mut_with_seperator(query, sep)
+            .map(|a| a.retain(f))
+    }
+
+    /// Keep only the values of the array for which `f` returns `true`
+    ///
+    /// See documentation of `TomlValueArrayExt::retain_with_seperator`
+    fn retain<F: FnMut(&Value) -> bool>(&mut self, query: &str, f: F) -> Result<()> {
+        self.retain_with_seperator(query, '.', f)
+    }
+
+    /// Sort the values of the array by the value at `key_path` in each of them, using a custom
+    /// seperator for both `query` and `key_path`
+    ///
+    /// # Semantics
+    ///
+    /// The `key_path` is a query which is read from every value of the array, so
+    /// `sort_by_key_path("servers", "address.port")` sorts the tables in `servers` by their port.
+    /// The sort is stable.
+    ///
+    /// Integers, floats, strings and booleans can be compared, but only with values of the same
+    /// type. If sorting fails, the array is not modified.
+    ///
+    /// # Return value
+    ///
+    /// * If the array was sorted, `Ok(())` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If `key_path` cannot be read from one of the values: error
+    ///     * If one of the values has no value at `key_path`: error
+    ///     * If two of the values at `key_path` cannot be compared: error
+    ///
+    fn sort_by_key_path_with_seperator(
+        &mut self,
+        query: &str,
+        key_path: &str,
+        sep: char,
+    ) -> Result<()> {
+        let array = self.array_mut_with_seperator(query, sep)?;
+
+        let keys = array
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| {
+                let missing = || {
+                    let query = format!("{}{sep}[{}]{sep}{}", query, idx, key_path, sep = sep);
+                    Error::NotAvailable(query)
+                };
+                value
+                    .read_with_seperator(key_path, sep)?
+                    .cloned()
+                    .ok_or_else(missing)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(first) = keys.first() {
+            for key in keys.iter() {
+                if compare(first, key).is_none() {
+                    let (a, b) = (name_of_val(first), name_of_val(key));
+                    return Err(Error::CannotCompare(key_path.to_owned(), a, b));
+                }
+            }
+        }
+
+        let mut values = keys.into_iter().zip(array.drain(..)).collect::<Vec<_>>();
+        // safe because all keys were checked to be comparable
+        values.sort_by(|(a, _), (b, _)| compare(a, b).unwrap());
+        array.extend(values.into_iter().map(|(_, value)| value));
+        Ok(())
+    }
+
+    /// Sort the values of the array by the value at `key_path` in each of them
+    ///
+    /// See documentation of `TomlValueArrayExt::sort_by_key_path_with_seperator`
+    fn sort_by_key_path(&mut self, query: &str, key_path: &str) -> Result<()> {
+        self.sort_by_key_path_with_seperator(query, key_path, '.')
+    }
+
+    /// Remove consecutive equal values from the array, like `Vec::dedup`, using a custom
+    /// seperator
+    fn dedup_with_seperator(&mut self, query: &str, sep: char) -> Result<()> {
+        self.array_mut_with_seperator(query, sep).map(|a| a.dedup())
+    }
+
+    /// Remove consecutive equal values from the array, like `Vec::dedup`
+    ///
+    /// See documentation of `TomlValueArrayExt::dedup_with_seperator`
+    fn dedup(&mut self, query: &str) -> Result<()> {
+        self.dedup_with_seperator(query, '.')
+    }
+}
+
+/// Compare two values of the same type
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => Some(a.total_cmp(b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+impl TomlValueArrayExt for Value {
+    fn array_mut_with_seperator(&mut self, query: &str, sep: char) -> Result<&mut Vec<Value>> {
+        match self.read_mut_with_seperator(query, sep)? {
+            Some(Value::Array(ref mut array)) => Ok(array),
+            Some(other) => Err(Error::NotAnArray(query.to_owned(), name_of_val(other))),
+            None => Err(Error::NotAvailable(query.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_push_and_pop() {
+        let mut toml: Value = toml_from_str("[table]\na = [1, 2]").unwrap();
+
+        toml.push("table.a", Value::Integer(3)).unwrap();
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[table]\na = [1, 2, 3]").unwrap()
+        );
+
+        assert_eq!(toml.pop("table.a").unwrap(), Some(Value::Integer(3)));
+        assert_eq!(toml.pop("table.a").unwrap(), Some(Value::Integer(2)));
+        assert_eq!(toml.pop("table.a").unwrap(), Some(Value::Integer(1)));
+        assert_eq!(toml.pop("table.a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_insert_at_and_remove_at() {
+        let mut toml: Value = toml_from_str("a = [1, 3]").unwrap();
+
+        toml.insert_at("a", 1, Value::Integer(2)).unwrap();
+        toml.insert_at("a", 3, Value::Integer(4)).unwrap();
+        assert_eq!(toml, toml_from_str::<Value>("a = [1, 2, 3, 4]").unwrap());
+
+        assert_eq!(toml.remove_at("a", 0).unwrap(), Value::Integer(1));
+        assert_eq!(toml, toml_from_str::<Value>("a = [2, 3, 4]").unwrap());
+    }
+
+    #[test]
+    fn test_insert_at_and_remove_at_out_of_bounds() {
+        let mut toml: Value = toml_from_str("a = [1]").unwrap();
+
+        let res = toml.insert_at("a", 2, Value::Integer(2));
+        assert!(is_match!(res, Err(Error::IndexOutOfBounds(2, 1))));
+
+        let res = toml.remove_at("a", 1);
+        assert!(is_match!(res, Err(Error::IndexOutOfBounds(1, 1))));
+
+        assert_eq!(toml, toml_from_str::<Value>("a = [1]").unwrap());
+    }
+
+    #[test]
+    fn test_retain_and_dedup() {
+        let mut toml: Value = toml_from_str("a = [1, 1, 2, 3, 3, 4, 1]").unwrap();
+
+        toml.dedup("a").unwrap();
+        assert_eq!(toml, toml_from_str::<Value>("a = [1, 2, 3, 4, 1]").unwrap());
+
+        toml.retain("a", |v| v.as_integer().map(|i| i % 2 == 1).unwrap_or(false))
+            .unwrap();
+        assert_eq!(toml, toml_from_str::<Value>("a = [1, 3, 1]").unwrap());
+    }
+
+    #[test]
+    fn test_sort_by_key_path() {
+        let mut toml: Value = toml_from_str(
+            r#"
+            [[servers]]
+            name = "b"
+            address = { port = 80 }
+
+            [[servers]]
+            name = "a"
+            address = { port = 8080 }
+
+            [[servers]]
+            name = "c"
+            address = { port = 80 }
+            "#,
+        )
+        .unwrap();
+
+        toml.sort_by_key_path("servers", "name").unwrap();
+        assert_eq!(
+            toml.read("servers.[0].name").unwrap(),
+            Some(&Value::String(String::from("a")))
+        );
+
+        toml.sort_by_key_path("servers", "address.port").unwrap();
+        let names = toml
+            .array_mut("servers")
+            .unwrap()
+            .iter()
+            .map(|s| s.get("name").unwrap().as_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_sort_by_key_path_errors() {
+        let mut toml: Value =
+            toml_from_str("a = [{ k = 1 }, { k = \"x\" }]\nb = [{ k = 1 }, { j = 2 }]").unwrap();
+        let before = toml.clone();
+
+        let res = toml.sort_by_key_path("a", "k");
+        assert!(is_match!(
+            res,
+            Err(Error::CannotCompare(_, "Integer", "String"))
+        ));
+
+        let res = toml.sort_by_key_path("b", "k");
+        match res {
+            Err(Error::NotAvailable(ref query)) => assert_eq!(query, "b.[1].k"),
+            _ => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(toml, before);
+    }
+
+    #[test]
+    fn test_sort_by_key_path_with_seperator() {
+        let mut toml: Value =
+            toml_from_str("[t]\nl = [{ a = { b = 2 } }, { a = { b = 1 } }]").unwrap();
+
+        toml.sort_by_key_path_with_seperator("t/l", "a/b", '/')
+            .unwrap();
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[t]\nl = [{ a = { b = 1 } }, { a = { b = 2 } }]").unwrap()
+        );
+
+        toml.push("t.l", Value::Table(Default::default())).unwrap();
+        let res = toml.sort_by_key_path_with_seperator("t/l", "a/b", '/');
+        match res {
+            Err(Error::NotAvailable(ref query)) => assert_eq!(query, "t/l/[2]/a/b"),
+            _ => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_array_operations_with_seperator() {
+        let mut toml: Value = toml_from_str("[t]\nl = [2, 4]").unwrap();
+
+        toml.push_with_seperator("t/l", '/', Value::Integer(4))
+            .unwrap();
+        toml.insert_at_with_seperator("t/l", '/', 0, Value::Integer(1))
+            .unwrap();
+        toml.dedup_with_seperator("t/l", '/').unwrap();
+        assert_eq!(
+            toml.remove_at_with_seperator("t/l", '/', 1).unwrap(),
+            Value::Integer(2)
+        );
+        toml.retain_with_seperator("t/l", '/', |v| v != &Value::Integer(1))
+            .unwrap();
+
+        assert_eq!(
+            toml.pop_with_seperator("t/l", '/').unwrap(),
+            Some(Value::Integer(4))
+        );
+        assert_eq!(toml.pop_with_seperator("t/l", '/').unwrap(), None);
+    }
+
+    #[test]
+    fn test_not_an_array() {
+        let mut toml: Value = toml_from_str("[table]\na = 1").unwrap();
+
+        let res = toml.push("table.a", Value::Integer(2));
+        match res {
+            Err(Error::NotAnArray(ref query, "Integer")) => assert_eq!(query, "table.a"),
+            _ => panic!("Unexpected result: {:?}", res),
+        }
+
+        let res = toml.pop("table.b");
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+    }
+}
@@ -0,0 +1,503 @@
+//! Reading and writing comments in toml_edit documents
+//!
+//! This module is only available with the `edit` feature.
+
+use toml_edit::{Decor, Document, Item, RawString};
+
+use crate::edit::{mismatch, resolve, resolve_mut, TomlEditReadExt};
+use crate::error::{Error, Result};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::tokenizer::Token;
+
+/// Extension trait for the comments of the values in a toml_edit document
+///
+/// Comments can be attached to keys of values and to table headers, either in front of them
+/// (leading comments, which can span multiple lines) or after them on the same line (trailing
+/// comments):
+///
+/// ```toml
+/// # leading comment of "server"
+/// [server] # trailing comment of "server"
+/// # leading comment of "server.port"
+/// port = 80 # trailing comment of "server.port"
+/// ```
+///
+/// The text of a comment is given and returned without the `# `. Values inside of inline tables
+/// and arrays, and tables without a header, cannot have comments.
+pub trait TomlEditCommentExt {
+    /// Extension function for reading the leading comment of a value using a custom seperator
+    ///
+    /// # Return value
+    ///
+    /// * If the value has a leading comment, `Ok(Some(text))` is returned, where the lines of
+    ///   the comment are joined with `\n`.
+    /// * If the value has no leading comment or there is no value, `Ok(None)` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query cannot be resolved: error
+    ///     * If the value cannot have a comment: error
+    ///
+    fn read_comment_with_seperator(&self, query: &str, sep: char) -> Result<Option<String>>;
+
+    /// Extension function for reading the leading comment of a value
+    ///
+    /// See documentation of `TomlEditCommentExt::read_comment_with_seperator`
+    fn read_comment(&self, query: &str) -> Result<Option<String>> {
+        self.read_comment_with_seperator(query, '.')
+    }
+
+    /// Extension function for setting the leading comment of a value using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// The comment replaces the comment lines in front of the key or table header. Blank lines
+    /// in front of the comment and the indentation of the key are kept. Every line of `text`
+    /// becomes a comment line. If `text` is empty, the comment is removed.
+    ///
+    /// # Return value
+    ///
+    /// * If the comment was set, `Ok(old)` is returned, where `old` is the replaced comment.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If there is no value at the query: error
+    ///     * If the value cannot have a comment: error
+    ///
+    fn set_comment_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        text: &str,
+    ) -> Result<Option<String>>;
+
+    /// Extension function for setting the leading comment of a value
+    ///
+    /// See documentation of `TomlEditCommentExt::set_comment_with_seperator`
+    fn set_comment(&mut self, query: &str, text: &str) -> Result<Option<String>> {
+        self.set_comment_with_seperator(query, '.', text)
+    }
+
+    /// Extension function for reading the trailing comment of a value using a custom seperator
+    ///
+    /// See documentation of `TomlEditCommentExt::read_comment_with_seperator`
+    fn read_trailing_comment_with_seperator(
+        &self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<String>>;
+
+    /// Extension function for reading the trailing comment of a value
+    ///
+    /// See documentation of `TomlEditCommentExt::read_comment_with_seperator`
+    fn read_trailing_comment(&self, query: &str) -> Result<Option<String>> {
+        self.read_trailing_comment_with_seperator(query, '.')
+    }
+
+    /// Extension function for setting the trailing comment of a value using a custom seperator
+    ///
+    /// Works like `TomlEditCommentExt::set_comment_with_seperator`, but fails with
+    /// `Error::MultiLineComment` if `text` has more than one line.
+    fn set_trailing_comment_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        text: &str,
+    ) -> Result<Option<String>>;
+
+    /// Extension function for setting the trailing comment of a value
+    ///
+    /// See documentation of `TomlEditCommentExt::set_trailing_comment_with_seperator`
+    fn set_trailing_comment(&mut self, query: &str, text: &str) -> Result<Option<String>> {
+        self.set_trailing_comment_with_seperator(query, '.', text)
+    }
+}
+
+/// Check whether `item` can have a comment
+fn commentable(item: &Item) -> bool {
+    match item {
+        Item::Value(_) => true,
+        // Implicit tables only get a header if they contain values
+        Item::Table(ref table) => {
+            !table.is_dotted() && (!table.is_implicit() || !table.get_values().is_empty())
+        }
+        _ => false,
+    }
+}
+
+/// The decor holding the leading or trailing comment of the value at `query`
+///
+/// Leading comments are in the prefix, trailing comments in the suffix of the decor. For a
+/// key-value pair, the leading comment is in the decor of the key, the trailing one in the decor
+/// of the value.
+fn decor<'doc>(
+    item: &'doc Item,
+    query: &str,
+    sep: char,
+    leading: bool,
+) -> Result<Option<&'doc Decor>> {
+    let mut tokens = tokenize_with_seperator(query, sep)?;
+    let (parent, last) = match tokens.pop_last() {
+        None => (item, Box::new(tokens)),
+        // safe because of resolve() guarantees
+        Some(last) => (resolve(item, &tokens, true)?.unwrap(), last),
+    };
+
+    if let Some(e) = mismatch(parent, &last) {
+        return Err(e);
+    }
+
+    match (parent, *last) {
+        (Item::Table(ref table), Token::Identifier { ref ident, .. }) => match table.get(ident) {
+            None => Ok(None),
+            Some(item) if !commentable(item) => Err(Error::CannotComment(query.to_owned())),
+            Some(Item::Value(_)) if leading => Ok(table.key_decor(ident)),
+            Some(Item::Value(ref value)) => Ok(Some(value.decor())),
+            Some(Item::Table(ref table)) => Ok(Some(table.decor())),
+            Some(_) => unreachable!("Only values and tables can have comments"),
+        },
+
+        (Item::ArrayOfTables(ref aot), Token::Index { idx, .. }) => match aot.get(idx) {
+            Some(table) => Ok(Some(table.decor())),
+            None => Err(Error::IndexOutOfBounds(idx, aot.len())),
+        },
+
+        _ => Err(Error::CannotComment(query.to_owned())),
+    }
+}
+
+/// The decor holding the leading or trailing comment of the value at `query`, mutably
+///
+/// See `decor()`. Fails with `Error::NotAvailable` if there is no value at `query`.
+fn decor_mut<'doc>(
+    item: &'doc mut Item,
+    query: &str,
+    sep: char,
+    leading: bool,
+) -> Result<&'doc mut Decor> {
+    let mut tokens = tokenize_with_seperator(query, sep)?;
+    let (parent, last) = match tokens.pop_last() {
+        None => (item, Box::new(tokens)),
+        // safe because of resolve_mut() guarantees
+        Some(last) => (resolve_mut(item, &tokens, true)?.unwrap(), last),
+    };
+
+    if let Some(e) = mismatch(parent, &last) {
+        return Err(e);
+    }
+
+    match (parent, *last) {
+        (Item::Table(ref mut table), Token::Identifier { ref ident, .. }) => {
+            let is_value = match table.get(ident) {
+                None => return Err(Error::NotAvailable(query.to_owned())),
+                Some(item) if !commentable(item) => {
+                    return Err(Error::CannotComment(query.to_owned()));
+                }
+                Some(item) => item.is_value(),
+            };
+
+            // safe because the key was just found
+            if leading && is_value {
+                Ok(table.key_decor_mut(ident).unwrap())
+            } else {
+                match table.get_mut(ident).unwrap() {
+                    Item::Value(ref mut value) => Ok(value.decor_mut()),
+                    Item::Table(ref mut table) => Ok(table.decor_mut()),
+                    _ => unreachable!("Only values and tables can have comments"),
+                }
+            }
+        }
+
+        (Item::ArrayOfTables(ref mut aot), Token::Index { idx, .. }) => {
+            let len = aot.len();
+            aot.get_mut(idx)
+                .map(|table| table.decor_mut())
+                .ok_or(Error::IndexOutOfBounds(idx, len))
+        }
+
+        _ => Err(Error::CannotComment(query.to_owned())),
+    }
+}
+
+/// The text of the comment lines in `raw`, without the `#` and the space after it
+fn comment_text(raw: Option<&RawString>) -> Option<String> {
+    let lines = raw
+        .and_then(|raw| raw.as_str())
+        .unwrap_or("")
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('#'))
+        .map(|line| line[1..].strip_prefix(' ').unwrap_or(&line[1..]))
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Replace the comment lines in the prefix `old` with the lines of `text`
+///
+/// The blank lines before the first comment line and the indentation after the last line are
+/// kept.
+fn leading_prefix(old: &str, text: &str) -> String {
+    let first_comment = old.find('#').unwrap_or(old.len());
+    let head = &old[..old[..first_comment].rfind('\n').map_or(0, |i| i + 1)];
+    let indent = &old[old.rfind('\n').map_or(0, |i| i + 1)..];
+
+    let comment = text
+        .lines()
+        .map(|line| format!("{}# {}\n", indent, line))
+        .collect::<String>();
+
+    format!("{}{}{}", head, comment, indent)
+}
+
+fn read_comment(item: &Item, query: &str, sep: char, leading: bool) -> Result<Option<String>> {
+    decor(item, query, sep, leading).map(|decor| {
+        decor.and_then(|decor| {
+            comment_text(if leading {
+                decor.prefix()
+            } else {
+                decor.suffix()
+            })
+        })
+    })
+}
+
+fn set_leading_comment(
+    item: &mut Item,
+    query: &str,
+    sep: char,
+    text: &str,
+) -> Result<Option<String>> {
+    let is_table = match item.read_with_seperator(query, sep)? {
+        Some(item) => item.is_table_like() && !item.is_value(),
+        None => false,
+    };
+
+    let decor = decor_mut(item, query, sep, true)?;
+    let old = comment_text(decor.prefix());
+
+    // Tables without a prefix are separated from the previous line by a blank line
+    let default = if is_table { "\n" } else { "" };
+    let prefix = decor
+        .prefix()
+        .and_then(|raw| raw.as_str())
+        .unwrap_or(default);
+    let prefix = leading_prefix(prefix, text);
+    decor.set_prefix(prefix);
+
+    Ok(old)
+}
+
+fn set_trailing_comment(
+    item: &mut Item,
+    query: &str,
+    sep: char,
+    text: &str,
+) -> Result<Option<String>> {
+    if text.lines().count() > 1 {
+        return Err(Error::MultiLineComment(query.to_owned()));
+    }
+
+    let decor = decor_mut(item, query, sep, false)?;
+    let old = comment_text(decor.suffix());
+    if text.is_empty() {
+        decor.set_suffix("");
+    } else {
+        decor.set_suffix(format!(" # {}", text));
+    }
+
+    Ok(old)
+}
+
+impl TomlEditCommentExt for Item {
+    fn read_comment_with_seperator(&self, query: &str, sep: char) -> Result<Option<String>> {
+        read_comment(self, query, sep, true)
+    }
+
+    fn set_comment_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        text: &str,
+    ) -> Result<Option<String>> {
+        set_leading_comment(self, query, sep, text)
+    }
+
+    fn read_trailing_comment_with_seperator(
+        &self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<String>> {
+        read_comment(self, query, sep, false)
+    }
+
+    fn set_trailing_comment_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        text: &str,
+    ) -> Result<Option<String>> {
+        set_trailing_comment(self, query, sep, text)
+    }
+}
+
+impl TomlEditCommentExt for Document {
+    fn read_comment_with_seperator(&self, query: &str, sep: char) -> Result<Option<String>> {
+        self.as_item().read_comment_with_seperator(query, sep)
+    }
+
+    fn set_comment_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        text: &str,
+    ) -> Result<Option<String>> {
+        self.as_item_mut()
+            .set_comment_with_seperator(query, sep, text)
+    }
+
+    fn read_trailing_comment_with_seperator(
+        &self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<String>> {
+        self.as_item()
+            .read_trailing_comment_with_seperator(query, sep)
+    }
+
+    fn set_trailing_comment_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        text: &str,
+    ) -> Result<Option<String>> {
+        self.as_item_mut()
+            .set_trailing_comment_with_seperator(query, sep, text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::edit::TomlEditInsertExt;
+    use toml_edit::value;
+
+    const CONFIG: &str = r#"# The server configuration
+[server] # main
+# Where to listen
+#  on all interfaces
+host = "0.0.0.0"
+port = 8080 # http
+
+[[clients]]
+name = "a"
+"#;
+
+    #[test]
+    fn test_read_comments() {
+        let config = CONFIG.parse::<Document>().unwrap();
+
+        assert_eq!(
+            config.read_comment("server").unwrap().as_deref(),
+            Some("The server configuration")
+        );
+        assert_eq!(
+            config.read_trailing_comment("server").unwrap().as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            config.read_comment("server.host").unwrap().as_deref(),
+            Some("Where to listen\n on all interfaces")
+        );
+        assert_eq!(
+            config
+                .read_trailing_comment("server.port")
+                .unwrap()
+                .as_deref(),
+            Some("http")
+        );
+        assert_eq!(config.read_comment("server.port").unwrap(), None);
+        assert_eq!(config.read_comment("clients.[0]").unwrap(), None);
+        assert_eq!(config.read_comment("server.missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_comments() {
+        let mut config = CONFIG.parse::<Document>().unwrap();
+
+        let old = config
+            .set_comment("server.port", "managed by deploy tool")
+            .unwrap();
+        assert_eq!(old, None);
+
+        let old = config.set_comment("server.host", "Listen here").unwrap();
+        assert_eq!(old.as_deref(), Some("Where to listen\n on all interfaces"));
+
+        config.set_trailing_comment("server.port", "").unwrap();
+        config.set_trailing_comment("server", "primary").unwrap();
+        config.set_comment("clients.[0]", "first\nclient").unwrap();
+
+        let expected = r#"# The server configuration
+[server] # primary
+# Listen here
+host = "0.0.0.0"
+# managed by deploy tool
+port = 8080
+
+# first
+# client
+[[clients]]
+name = "a"
+"#;
+        assert_eq!(config.to_string(), expected);
+    }
+
+    #[test]
+    fn test_set_comment_keeps_blank_lines_and_indentation() {
+        let mut config: Document = "a = 1\n\n  # old\n  b = 2\n".parse().unwrap();
+
+        config.set_comment("b", "new").unwrap();
+        assert_eq!(config.to_string(), "a = 1\n\n  # new\n  b = 2\n");
+
+        config.set_comment("b", "").unwrap();
+        assert_eq!(config.to_string(), "a = 1\n\n  b = 2\n");
+    }
+
+    #[test]
+    fn test_set_comment_on_inserted_table() {
+        let mut config: Document = "a = 1\n".parse().unwrap();
+
+        config.insert("table.b", value(2)).unwrap();
+        config.set_comment("table", "generated").unwrap();
+
+        assert_eq!(config.to_string(), "a = 1\n\n# generated\n[table]\nb = 2\n");
+    }
+
+    #[test]
+    fn test_comment_errors() {
+        let mut config: Document = "point = { x = 1 }\nlist = [1]\n[a.b]\nc = 1\n"
+            .parse()
+            .unwrap();
+
+        assert!(is_match!(
+            config.read_comment("point.x"),
+            Err(Error::CannotComment(_))
+        ));
+        assert!(is_match!(
+            config.set_comment("list.[0]", "x"),
+            Err(Error::CannotComment(_))
+        ));
+        assert!(is_match!(
+            config.set_comment("a", "implicit"),
+            Err(Error::CannotComment(_))
+        ));
+        assert!(is_match!(
+            config.set_comment("missing", "x"),
+            Err(Error::NotAvailable(_))
+        ));
+        assert!(is_match!(
+            config.set_trailing_comment("point", "two\nlines"),
+            Err(Error::MultiLineComment(_))
+        ));
+    }
+}
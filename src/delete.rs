@@ -4,6 +4,7 @@ use toml::Value;
 use crate::error::{Error, Result};
 use crate::tokenizer::tokenize_with_seperator;
 use crate::tokenizer::Token;
+use crate::util::{get_path, path_to_query, remove_path, token_path, Segment};
 
 pub trait TomlValueDeleteExt {
     /// Extension function for deleting a value in the current toml::Value document
@@ -40,133 +41,174 @@ pub trait TomlValueDeleteExt {
     fn delete(&mut self, query: &str) -> Result<Option<Value>> {
         self.delete_with_seperator(query, '.')
     }
+
+    /// Extension function for deleting a value and everything below it in the current
+    /// toml::Value document using a custom seperator.
+    ///
+    /// # Semantics
+    ///
+    /// Other than `TomlValueDeleteExt::delete_with_seperator`, the function also deletes
+    /// non-empty data structures. So deleting `table` from
+    ///
+    /// ```toml
+    /// [table]
+    /// array = [ 1 ]
+    /// ```
+    ///
+    /// removes the whole table.
+    ///
+    /// If `prune` is true, parent tables which are empty after the deletion are deleted as well,
+    /// up to (but not including) the document root. Arrays and tables which are elements of
+    /// arrays are never pruned.
+    ///
+    /// # Return value
+    ///
+    /// If the delete operation worked correctly, `Ok(Option<Value>)` is returned, where the
+    /// `Option<Value>` is the removed value, or `None` if there was no value.
+    ///
+    /// On failure, `Err(e)` is returned
+    fn delete_recursive_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        prune: bool,
+    ) -> Result<Option<Value>>;
+
+    /// Extension function for deleting a value and everything below it from the current
+    /// toml::Value document
+    ///
+    /// See documentation of `TomlValueDeleteExt::delete_recursive_with_seperator`
+    fn delete_recursive(&mut self, query: &str) -> Result<Option<Value>> {
+        self.delete_recursive_with_seperator(query, '.', false)
+    }
+
+    /// Extension function for deleting a value and everything below it from the current
+    /// toml::Value document, deleting parent tables which become empty
+    ///
+    /// See documentation of `TomlValueDeleteExt::delete_recursive_with_seperator`
+    fn delete_recursive_and_prune(&mut self, query: &str) -> Result<Option<Value>> {
+        self.delete_recursive_with_seperator(query, '.', true)
+    }
+
+    /// Extension function for deleting every value matching a pattern in the current
+    /// toml::Value document using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// The pattern is a query which may contain the wildcards `*` and `**`, see documentation of
+    /// `TomlValueSetExt::set_all_with_seperator`.
+    ///
+    /// Matching values are deleted like `TomlValueDeleteExt::delete_recursive` does, so
+    /// non-empty data structures are deleted as well. If a matching value is inside of another
+    /// matching value, only the outer one is deleted. Values are deleted from the end of the
+    /// document to its beginning, so deleting an array element does not shift the indexes of
+    /// the array elements which are deleted afterwards.
+    ///
+    /// # Return value
+    ///
+    /// * If the delete operation worked correctly, `Ok(deleted)` is returned, where `deleted`
+    ///   contains the query (in the document before the deletion) and the value of every deleted
+    ///   value, in document order.
+    /// * On failure, `Err(e)` is returned and the document is not modified:
+    ///     * If the pattern cannot be parsed: error
+    ///     * If the key of a matching value cannot be expressed in a query: error
+    ///
+    fn delete_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+    ) -> Result<Vec<(String, Value)>>;
+
+    /// Extension function for deleting every value matching a pattern in the current
+    /// toml::Value document
+    ///
+    /// See documentation of `TomlValueDeleteExt::delete_all_with_seperator`
+    fn delete_all(&mut self, pattern: &str) -> Result<Vec<(String, Value)>> {
+        self.delete_all_with_seperator(pattern, '.')
+    }
 }
 
 impl TomlValueDeleteExt for Value {
     fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
         use crate::resolver::mut_resolver::resolve;
-        use std::ops::Index;
 
         let mut tokens = tokenize_with_seperator(query, sep)?;
-        let last_token = tokens.pop_last();
-
-        /// Check whether a structure (Table/Array) is empty. If the Value has not these types,
-        /// the default value is returned
-        #[inline]
-        fn is_empty(val: Option<&Value>, default: bool) -> bool {
-            val.map(|v| match v {
-                Value::Table(ref tab) => tab.is_empty(),
-                Value::Array(ref arr) => arr.is_empty(),
-                _ => default,
-            })
-            .unwrap_or(default)
-        }
+        let (val, last) = match tokens.pop_last() {
+            None => (self, Box::new(tokens)),
+            // safe because of resolve() guarantees
+            Some(last) => (resolve(self, &tokens, true)?.unwrap(), last),
+        };
 
-        #[inline]
-        fn is_table(val: Option<&Value>) -> bool {
-            val.map(|v| is_match!(v, &Value::Table(_))).unwrap_or(false)
-        }
-
-        #[inline]
-        fn is_array(val: Option<&Value>) -> bool {
-            val.map(|v| is_match!(v, &Value::Array(_))).unwrap_or(false)
-        }
+        crate::queryable::delete_last(val, *last)
+    }
 
-        #[inline]
-        fn name_of_val(val: Option<&Value>) -> &'static str {
-            val.map(crate::util::name_of_val).unwrap_or("None")
-        }
+    fn delete_recursive_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        prune: bool,
+    ) -> Result<Option<Value>> {
+        use crate::resolver::mut_resolver::resolve;
 
-        match last_token {
-            None => match self {
-                Value::Table(ref mut tab) => match tokens {
-                    Token::Identifier { ident, .. } => {
-                        if is_empty(tab.get(&ident), true) {
-                            Ok(tab.remove(&ident))
-                        } else if is_table(tab.get(&ident)) {
-                            Err(Error::CannotDeleteNonEmptyTable(Some(ident.clone())))
-                        } else if is_array(tab.get(&ident)) {
-                            Err(Error::CannotDeleteNonEmptyArray(Some(ident.clone())))
-                        } else {
-                            let act = name_of_val(tab.get(&ident));
-                            let tbl = "table";
-                            Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                        }
-                    }
-                    _ => Ok(None),
-                },
-                Value::Array(ref mut arr) => match tokens {
-                    Token::Identifier { ident, .. } => Err(Error::NoIdentifierInArray(ident)),
-                    Token::Index { idx, .. } => {
-                        if is_empty(Some(arr.index(idx)), true) {
-                            Ok(Some(arr.remove(idx)))
-                        } else if is_table(Some(arr.index(idx))) {
-                            Err(Error::CannotDeleteNonEmptyTable(None))
-                        } else if is_array(Some(arr.index(idx))) {
-                            Err(Error::CannotDeleteNonEmptyArray(None))
-                        } else {
-                            let act = name_of_val(Some(arr.index(idx)));
-                            let tbl = "table";
-                            Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                        }
-                    }
-                },
-                _ => {
-                    let kind = match tokens {
-                        Token::Identifier { ident, .. } => Error::QueryingValueAsTable(ident),
-                        Token::Index { idx, .. } => Error::QueryingValueAsArray(idx),
-                    };
-                    Err(kind)
-                }
+        let mut tokens = tokenize_with_seperator(query, sep)?;
+        let path = token_path(&tokens);
+        let (val, last) = match tokens.pop_last() {
+            None => (&mut *self, Box::new(tokens)),
+            // safe because of resolve() guarantees
+            Some(last) => (resolve(self, &tokens, true)?.unwrap(), last),
+        };
+
+        let removed = match *last {
+            Token::Identifier { ident, .. } => match val {
+                Value::Table(ref mut tab) => tab.remove(&ident),
+                Value::Array(_) => return Err(Error::NoIdentifierInArray(ident)),
+                _ => return Err(Error::QueryingValueAsTable(ident)),
+            },
+            Token::Index { idx, .. } => match val {
+                Value::Array(ref mut arr) if idx < arr.len() => Some(arr.remove(idx)),
+                Value::Array(_) => None,
+                Value::Table(_) => return Err(Error::NoIndexInTable(idx)),
+                _ => return Err(Error::QueryingValueAsArray(idx)),
             },
-            Some(last_token) => {
-                let val = resolve(self, &tokens, true)?.unwrap(); // safe because of resolve() guarantees
-                match val {
-                    Value::Table(ref mut tab) => match *last_token {
-                        Token::Identifier { ref ident, .. } => {
-                            if is_empty(tab.get(ident), true) {
-                                Ok(tab.remove(ident))
-                            } else if is_table(tab.get(ident)) {
-                                Err(Error::CannotDeleteNonEmptyTable(Some(ident.clone())))
-                            } else if is_array(tab.get(ident)) {
-                                Err(Error::CannotDeleteNonEmptyArray(Some(ident.clone())))
-                            } else {
-                                let act = name_of_val(tab.get(ident));
-                                let tbl = "table";
-                                Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                            }
-                        }
-                        Token::Index { idx, .. } => Err(Error::NoIndexInTable(idx)),
-                    },
-                    Value::Array(ref mut arr) => match *last_token {
-                        Token::Identifier { ident, .. } => Err(Error::NoIdentifierInArray(ident)),
-                        Token::Index { idx, .. } => {
-                            if idx > arr.len() {
-                                return Err(Error::ArrayIndexOutOfBounds(idx, arr.len()));
-                            }
-                            if is_empty(Some(&arr.index(idx)), true) {
-                                Ok(Some(arr.remove(idx)))
-                            } else if is_table(Some(&arr.index(idx))) {
-                                Err(Error::CannotDeleteNonEmptyTable(None))
-                            } else if is_array(Some(&arr.index(idx))) {
-                                Err(Error::CannotDeleteNonEmptyArray(None))
-                            } else {
-                                let act = name_of_val(Some(arr.index(idx)));
-                                let tbl = "table";
-                                Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                            }
-                        }
-                    },
-                    _ => {
-                        let kind = match *last_token {
-                            Token::Identifier { ident, .. } => Error::QueryingValueAsTable(ident),
-                            Token::Index { idx, .. } => Error::QueryingValueAsArray(idx),
-                        };
-                        Err(kind)
+        };
+
+        if prune && removed.is_some() {
+            // Tables in arrays are not pruned, as that would shift the indexes of the array
+            for len in (1..path.len()).rev() {
+                match (get_path(self, &path[..len]), &path[len - 1]) {
+                    (Some(Value::Table(ref tab)), Segment::Key(_)) if tab.is_empty() => {
+                        let _ = remove_path(self, &path[..len]);
                     }
+                    _ => break,
                 }
             }
         }
+
+        Ok(removed)
+    }
+
+    fn delete_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+    ) -> Result<Vec<(String, Value)>> {
+        use crate::resolver::multi_resolver::resolve_outermost;
+
+        let tokens = tokenize_with_seperator(pattern, sep)?;
+        let paths = resolve_outermost(self, &tokens);
+        let queries = paths
+            .iter()
+            .map(|path| path_to_query(path, sep))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut deleted = paths
+            .iter()
+            .rev()
+            .map(|path| remove_path(self, path).unwrap()) // safe because resolved before
+            .collect::<Vec<_>>();
+        deleted.reverse();
+
+        Ok(queries.into_iter().zip(deleted).collect())
     }
 }
 
@@ -689,6 +731,22 @@ mod test {
         assert!(is_match!(res, Error::CannotDeleteNonEmptyTable(None)));
     }
 
+    #[test]
+    fn test_delete_nonexistent_idx_from_top_level_array() {
+        let mut toml = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+
+        let res = toml.delete_with_seperator(&String::from("[5]"), '.');
+
+        assert!(res.is_err());
+
+        let res = res.unwrap_err();
+        assert!(is_match!(res, Error::ArrayIndexOutOfBounds(5, 2)));
+        assert_eq!(
+            toml,
+            Value::Array(vec![Value::Integer(1), Value::Integer(2)])
+        );
+    }
+
     #[test]
     fn test_delete_from_value_like_it_was_table() {
         let mut toml: Value = toml_from_str(
@@ -722,4 +780,195 @@ mod test {
         let res = res.unwrap_err();
         assert!(is_match!(res, Error::QueryingValueAsArray(0)));
     }
+
+    #[test]
+    fn test_delete_recursive_non_empty_table() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        [table]
+        a = 1
+        array = [ 1 ]
+
+        [table.inner]
+        b = 2
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.delete_recursive("table");
+
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert!(is_match!(res, Some(Value::Table(_))));
+        match res {
+            Some(Value::Table(ref t)) => assert_eq!(t.len(), 3),
+            _ => panic!("What just happened?"),
+        }
+
+        match toml {
+            Value::Table(tab) => assert!(tab.is_empty()),
+            _ => unreachable!("Strange things are happening"),
+        }
+    }
+
+    #[test]
+    fn test_delete_recursive_non_empty_array_element() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        array = [ [ 1 ], [ 2 ] ]
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.delete_recursive("array.[0]");
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Some(Value::Array(vec![Value::Integer(1)])));
+
+        let expected: Value = toml_from_str("array = [ [ 2 ] ]").unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_delete_recursive_nonexistent() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        array = []
+        [table]
+        "#,
+        )
+        .unwrap();
+
+        assert!(is_match!(toml.delete_recursive("table.a"), Ok(None)));
+        assert!(is_match!(toml.delete_recursive("array.[0]"), Ok(None)));
+        assert!(is_match!(
+            toml.delete_recursive("nothing.a"),
+            Err(Error::IdentifierNotFoundInDocument(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_recursive_and_prune() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        [a]
+        x = 1
+
+        [a.b.c]
+        d = [ 1 ]
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.delete_recursive_and_prune("a.b.c.d");
+
+        assert!(res.is_ok());
+        assert!(is_match!(res.unwrap(), Some(Value::Array(_))));
+
+        let expected: Value = toml_from_str("[a]\nx = 1").unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_delete_recursive_and_prune_keeps_root_and_arrays() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        [[array]]
+        [array.table]
+        a = 1
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.delete_recursive_and_prune("array.[0].table.a");
+        assert!(res.is_ok());
+
+        let expected: Value = toml_from_str("array = [ {} ]").unwrap();
+        assert_eq!(toml, expected);
+
+        let res = toml.delete_recursive_and_prune("array");
+        assert!(res.is_ok());
+
+        match toml {
+            Value::Table(tab) => assert!(tab.is_empty()),
+            _ => unreachable!("Strange things are happening"),
+        }
+    }
+
+    #[test]
+    fn test_delete_all_recursive_wildcard() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        password = "a"
+
+        [db]
+        user = "user"
+        password = "b"
+
+        [[servers]]
+        password = "c"
+
+        [[servers]]
+        name = "d"
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.delete_all("**.password");
+
+        assert!(res.is_ok());
+        let queries = res
+            .unwrap()
+            .into_iter()
+            .map(|(query, _)| query)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queries,
+            vec!["db.password", "password", "servers.[0].password"]
+        );
+
+        let expected: Value = toml_from_str(
+            r#"
+        [db]
+        user = "user"
+
+        [[servers]]
+
+        [[servers]]
+        name = "d"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_delete_all_array_elements() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        array = [ [ 1 ], [ 2, 3 ], [ 4 ] ]
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.delete_all("array.*.[0]");
+
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![
+                (String::from("array.[0].[0]"), Value::Integer(1)),
+                (String::from("array.[1].[0]"), Value::Integer(2)),
+                (String::from("array.[2].[0]"), Value::Integer(4)),
+            ]
+        );
+
+        let res = toml.delete_all("array.*");
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap().len(), 3);
+
+        let expected: Value = toml_from_str("array = []").unwrap();
+        assert_eq!(toml, expected);
+    }
 }
@@ -0,0 +1,340 @@
+//! A serde Deserializer borrowing from a `toml::Value`
+//!
+//! `toml::Value` itself only deserializes by value, so deserializing a part of a document with
+//! it requires cloning that part. The `ValueDeserializer` deserializes from a reference instead,
+//! and lends strings and keys of the document to types which borrow them.
+//!
+//! It behaves like the deserializer of `toml::Value`: Datetimes are deserialized as strings and
+//! enums only from strings naming a unit variant.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::slice;
+
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, IntoDeserializer};
+use serde::forward_to_deserialize_any;
+use toml::de::Error;
+use toml::map;
+use toml::Value;
+
+use crate::util::Segment;
+
+/// The paths of the values which were ignored while deserializing
+pub(crate) type Unused = Rc<RefCell<Vec<Vec<Segment>>>>;
+
+/// The path of the value being deserialized, for recording it if it is ignored
+#[derive(Debug, Clone)]
+struct Tracker {
+    path: Vec<Segment>,
+    unused: Unused,
+}
+
+impl Tracker {
+    fn child(tracker: &Option<Tracker>, segment: Segment) -> Option<Tracker> {
+        tracker.as_ref().map(|t| {
+            let mut path = t.path.clone();
+            path.push(segment);
+            Tracker {
+                path,
+                unused: t.unused.clone(),
+            }
+        })
+    }
+}
+
+/// Deserializer over a borrowed `toml::Value`
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serde;
+/// # extern crate toml;
+/// # extern crate toml_query;
+/// use serde::Deserialize;
+/// use toml_query::deserializer::ValueDeserializer;
+///
+/// let value = toml::Value::String(String::from("borrowed"));
+/// let s = <&str>::deserialize(ValueDeserializer::new(&value)).unwrap();
+/// assert_eq!(s, "borrowed");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ValueDeserializer<'de> {
+    value: &'de Value,
+    tracker: Option<Tracker>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    pub fn new(value: &'de Value) -> Self {
+        ValueDeserializer {
+            value,
+            tracker: None,
+        }
+    }
+
+    /// Deserializer recording the paths of the values the deserialized type ignores in `unused`
+    ///
+    /// `path` is the path of `value` in its document.
+    pub(crate) fn tracking(value: &'de Value, path: Vec<Segment>, unused: &Unused) -> Self {
+        let tracker = Tracker {
+            path,
+            unused: unused.clone(),
+        };
+
+        ValueDeserializer {
+            value,
+            tracker: Some(tracker),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::Boolean(v) => visitor.visit_bool(*v),
+            Value::Integer(n) => visitor.visit_i64(*n),
+            Value::Float(n) => visitor.visit_f64(*n),
+            Value::String(ref v) => visitor.visit_borrowed_str(v),
+            Value::Datetime(ref v) => visitor.visit_string(v.to_string()),
+            Value::Array(ref v) => {
+                let mut deserializer = SeqDeserializer {
+                    iter: v.iter().enumerate(),
+                    tracker: self.tracker,
+                };
+                let seq = visitor.visit_seq(&mut deserializer)?;
+                if deserializer.iter.len() == 0 {
+                    Ok(seq)
+                } else {
+                    Err(de::Error::invalid_length(
+                        v.len(),
+                        &"fewer elements in array",
+                    ))
+                }
+            }
+            Value::Table(ref v) => {
+                let mut deserializer = MapDeserializer {
+                    iter: v.iter(),
+                    value: None,
+                    tracker: self.tracker,
+                };
+                let map = visitor.visit_map(&mut deserializer)?;
+                if deserializer.iter.len() == 0 {
+                    Ok(map)
+                } else {
+                    Err(de::Error::invalid_length(v.len(), &"fewer elements in map"))
+                }
+            }
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::String(ref variant) => {
+                visitor.visit_enum(BorrowedStrDeserializer::new(variant.as_str()))
+            }
+            _ => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"string only",
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let Some(tracker) = self.tracker {
+            tracker.unused.borrow_mut().push(tracker.path);
+        }
+
+        visitor.visit_unit()
+    }
+
+    // A missing value is a missing field, so a present value is always `Some`
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
+        bytes byte_buf map unit_struct tuple_struct struct
+        tuple identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct SeqDeserializer<'de> {
+    iter: std::iter::Enumerate<slice::Iter<'de, Value>>,
+    tracker: Option<Tracker>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((idx, value)) => {
+                let tracker = Tracker::child(&self.tracker, Segment::Index(idx));
+                seed.deserialize(ValueDeserializer { value, tracker })
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer<'de> {
+    iter: map::Iter<'de>,
+    value: Option<(&'de str, &'de Value)>,
+    tracker: Option<Tracker>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(BorrowedStrDeserializer::new(key.as_str()))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some((key, value)) => {
+                let tracker = Tracker::child(&self.tracker, Segment::Key(key.to_owned()));
+                seed.deserialize(ValueDeserializer { value, tracker })
+            }
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use toml::from_str as toml_from_str;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server<'a> {
+        name: &'a str,
+        ports: Vec<u16>,
+        #[serde(borrow)]
+        tags: HashMap<&'a str, &'a str>,
+        mode: Mode,
+        backup: Option<bool>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Active,
+        Passive,
+    }
+
+    #[test]
+    fn test_borrowing() {
+        let value: Value = toml_from_str(
+            r#"
+        name = "alpha"
+        ports = [80, 443]
+        mode = "passive"
+        [tags]
+        env = "prod"
+        "#,
+        )
+        .unwrap();
+
+        let server = Server::deserialize(ValueDeserializer::new(&value)).unwrap();
+        assert_eq!(server.name, "alpha");
+        assert_eq!(server.ports, vec![80, 443]);
+        assert_eq!(server.tags.get("env"), Some(&"prod"));
+        assert_eq!(server.mode, Mode::Passive);
+        assert_eq!(server.backup, None);
+
+        // The strings point into the document
+        match value.get("name") {
+            Some(Value::String(ref s)) => assert_eq!(s.as_ptr(), server.name.as_ptr()),
+            _ => panic!("name is not a string"),
+        }
+    }
+
+    #[test]
+    fn test_same_as_value() {
+        let value: Value = toml_from_str(
+            r#"
+        a = 1
+        b = 1979-05-27T07:32:00Z
+        c = [1.5, 2.5]
+        "#,
+        )
+        .unwrap();
+
+        let borrowed =
+            HashMap::<String, Value>::deserialize(ValueDeserializer::new(&value)).unwrap();
+        let owned: HashMap<String, Value> = value.clone().try_into().unwrap();
+        assert_eq!(borrowed, owned);
+
+        let borrowed = <(i64, i64)>::deserialize(ValueDeserializer::new(&value["c"]));
+        let owned: std::result::Result<(i64, i64), _> = value["c"].clone().try_into();
+        assert!(borrowed.is_err());
+        assert!(owned.is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Previewing modifications on a toml::Value document without applying them
+
+use toml::Value;
+
+use crate::delete::TomlValueDeleteExt;
+use crate::error::Result;
+use crate::insert::TomlValueInsertExt;
+use crate::patch::{Operation, Patch};
+use crate::set::TomlValueSetExt;
+use crate::tokenizer::tokenize_with_seperator;
+use crate::util::{get_path, path_to_query, token_path};
+
+/// A change a modification would make to a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The query of the changed value
+    pub query: String,
+
+    /// The value at the query before the modification, `None` if there is none
+    pub old: Option<Value>,
+
+    /// The value at the query after the modification, `None` if it is deleted
+    pub new: Option<Value>,
+
+    /// The queries of the intermediate tables which would be created, outermost first
+    pub created_tables: Vec<String>,
+}
+
+pub trait TomlValueDryRunExt {
+    /// Extension function for previewing a set operation on the current toml::Value document
+    /// using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// All dry-run functions work on a copy of the document, so they fail exactly when the
+    /// modification would fail, but the document itself is never modified.
+    ///
+    /// # Return value
+    ///
+    /// * If the set operation would work, `Ok(change)` is returned.
+    /// * If the set operation would fail, its error is returned, see
+    ///   `TomlValueSetExt::set_with_seperator`
+    ///
+    fn dry_run_set_with_seperator(&self, query: &str, sep: char, value: Value) -> Result<Change>;
+
+    /// Extension function for previewing a set operation on the current toml::Value document
+    ///
+    /// See documentation of `TomlValueDryRunExt::dry_run_set_with_seperator`
+    fn dry_run_set(&self, query: &str, value: Value) -> Result<Change> {
+        self.dry_run_set_with_seperator(query, '.', value)
+    }
+
+    /// Extension function for previewing an insert operation on the current toml::Value
+    /// document using a custom seperator
+    ///
+    /// # Return value
+    ///
+    /// * If the insert operation would work, `Ok(change)` is returned. The change lists the
+    ///   tables the insert operation would create.
+    /// * If the insert operation would fail, its error is returned, see
+    ///   `TomlValueInsertExt::insert_with_seperator`
+    ///
+    fn dry_run_insert_with_seperator(&self, query: &str, sep: char, value: Value)
+        -> Result<Change>;
+
+    /// Extension function for previewing an insert operation on the current toml::Value
+    /// document
+    ///
+    /// See documentation of `TomlValueDryRunExt::dry_run_insert_with_seperator`
+    fn dry_run_insert(&self, query: &str, value: Value) -> Result<Change> {
+        self.dry_run_insert_with_seperator(query, '.', value)
+    }
+
+    /// Extension function for previewing a delete operation on the current toml::Value
+    /// document using a custom seperator
+    ///
+    /// # Return value
+    ///
+    /// * If the delete operation would work, `Ok(change)` is returned.
+    /// * If the delete operation would fail, its error is returned, see
+    ///   `TomlValueDeleteExt::delete_with_seperator`
+    ///
+    fn dry_run_delete_with_seperator(&self, query: &str, sep: char) -> Result<Change>;
+
+    /// Extension function for previewing a delete operation on the current toml::Value
+    /// document
+    ///
+    /// See documentation of `TomlValueDryRunExt::dry_run_delete_with_seperator`
+    fn dry_run_delete(&self, query: &str) -> Result<Change> {
+        self.dry_run_delete_with_seperator(query, '.')
+    }
+
+    /// Extension function for previewing a bulk set operation on the current toml::Value
+    /// document using a custom seperator
+    ///
+    /// Returns one change per replaced value, see `TomlValueSetExt::set_all_with_seperator`.
+    fn dry_run_set_all_with_seperator(
+        &self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<Change>>;
+
+    /// Extension function for previewing a bulk set operation on the current toml::Value
+    /// document
+    ///
+    /// See documentation of `TomlValueDryRunExt::dry_run_set_all_with_seperator`
+    fn dry_run_set_all(&self, pattern: &str, value: Value) -> Result<Vec<Change>> {
+        self.dry_run_set_all_with_seperator(pattern, '.', value)
+    }
+
+    /// Extension function for previewing a bulk delete operation on the current toml::Value
+    /// document using a custom seperator
+    ///
+    /// Returns one change per deleted value, see `TomlValueDeleteExt::delete_all_with_seperator`.
+    fn dry_run_delete_all_with_seperator(&self, pattern: &str, sep: char) -> Result<Vec<Change>>;
+
+    /// Extension function for previewing a bulk delete operation on the current toml::Value
+    /// document
+    ///
+    /// See documentation of `TomlValueDryRunExt::dry_run_delete_all_with_seperator`
+    fn dry_run_delete_all(&self, pattern: &str) -> Result<Vec<Change>> {
+        self.dry_run_delete_all_with_seperator(pattern, '.')
+    }
+
+    /// Extension function for previewing the application of a `Patch` to the current
+    /// toml::Value document
+    ///
+    /// Returns one change per operation of the patch, in order. Each change describes the
+    /// document after all previous operations were applied.
+    fn dry_run_patch(&self, patch: &Patch) -> Result<Vec<Change>>;
+}
+
+/// Apply `operation` to the scratch copy `doc` and describe the change
+fn run(doc: &mut Value, operation: &Operation, sep: char) -> Result<Change> {
+    let query = operation.query().to_owned();
+
+    match operation {
+        Operation::Set { ref value, .. } => {
+            let old = doc.set_with_seperator(&query, sep, value.clone())?;
+            Ok(Change {
+                query,
+                old,
+                new: Some(value.clone()),
+                created_tables: vec![],
+            })
+        }
+
+        Operation::Insert { ref value, .. } => {
+            let path = token_path(&tokenize_with_seperator(&query, sep)?);
+            let created_tables = (1..path.len())
+                .filter(|i| get_path(doc, &path[..*i]).is_none())
+                .map(|i| path_to_query(&path[..i], sep))
+                .collect::<Result<Vec<_>>>()?;
+
+            let old = doc.insert_with_seperator(&query, sep, value.clone())?;
+            Ok(Change {
+                query,
+                old,
+                new: Some(value.clone()),
+                created_tables,
+            })
+        }
+
+        Operation::Delete { .. } => {
+            let old = doc.delete_with_seperator(&query, sep)?;
+            Ok(Change {
+                query,
+                old,
+                new: None,
+                created_tables: vec![],
+            })
+        }
+    }
+}
+
+impl TomlValueDryRunExt for Value {
+    fn dry_run_set_with_seperator(&self, query: &str, sep: char, value: Value) -> Result<Change> {
+        let query = query.to_owned();
+        run(&mut self.clone(), &Operation::Set { query, value }, sep)
+    }
+
+    fn dry_run_insert_with_seperator(
+        &self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Change> {
+        let query = query.to_owned();
+        run(&mut self.clone(), &Operation::Insert { query, value }, sep)
+    }
+
+    fn dry_run_delete_with_seperator(&self, query: &str, sep: char) -> Result<Change> {
+        let query = query.to_owned();
+        run(&mut self.clone(), &Operation::Delete { query }, sep)
+    }
+
+    fn dry_run_set_all_with_seperator(
+        &self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<Change>> {
+        let replaced = self
+            .clone()
+            .set_all_with_seperator(pattern, sep, value.clone())?;
+
+        Ok(replaced
+            .into_iter()
+            .map(|(query, old)| Change {
+                query,
+                old: Some(old),
+                new: Some(value.clone()),
+                created_tables: vec![],
+            })
+            .collect())
+    }
+
+    fn dry_run_delete_all_with_seperator(&self, pattern: &str, sep: char) -> Result<Vec<Change>> {
+        let deleted = self.clone().delete_all_with_seperator(pattern, sep)?;
+
+        Ok(deleted
+            .into_iter()
+            .map(|(query, old)| Change {
+                query,
+                old: Some(old),
+                new: None,
+                created_tables: vec![],
+            })
+            .collect())
+    }
+
+    fn dry_run_patch(&self, patch: &Patch) -> Result<Vec<Change>> {
+        let mut doc = self.clone();
+
+        patch
+            .operations()
+            .iter()
+            .map(|operation| run(&mut doc, operation, patch.seperator()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_dry_run_set() {
+        let toml: Value = toml_from_str("[server]\nport = 80").unwrap();
+
+        let change = toml
+            .dry_run_set("server.port", Value::Integer(8080))
+            .unwrap();
+
+        assert_eq!(
+            change,
+            Change {
+                query: String::from("server.port"),
+                old: Some(Value::Integer(80)),
+                new: Some(Value::Integer(8080)),
+                created_tables: vec![],
+            }
+        );
+        assert_eq!(toml, toml_from_str::<Value>("[server]\nport = 80").unwrap());
+    }
+
+    #[test]
+    fn test_dry_run_insert_lists_created_tables() {
+        let toml: Value = toml_from_str("[a]").unwrap();
+
+        let change = toml.dry_run_insert("a.b.c.d", Value::Integer(1)).unwrap();
+
+        assert_eq!(change.old, None);
+        assert_eq!(change.new, Some(Value::Integer(1)));
+        assert_eq!(change.created_tables, vec!["a.b", "a.b.c"]);
+        assert_eq!(toml, toml_from_str::<Value>("[a]").unwrap());
+    }
+
+    #[test]
+    fn test_dry_run_delete() {
+        let toml: Value = toml_from_str("a = 1\nb = [1]").unwrap();
+
+        let change = toml.dry_run_delete("a").unwrap();
+        assert_eq!(change.old, Some(Value::Integer(1)));
+        assert_eq!(change.new, None);
+
+        let res = toml.dry_run_delete("b");
+        assert!(is_match!(res, Err(Error::CannotDeleteNonEmptyArray(_))));
+
+        assert_eq!(toml, toml_from_str::<Value>("a = 1\nb = [1]").unwrap());
+    }
+
+    #[test]
+    fn test_dry_run_bulk_operations() {
+        let toml: Value = toml_from_str("[a]\nx = 1\n[b]\nx = 2\ny = 3").unwrap();
+
+        let changes = toml.dry_run_set_all("*.x", Value::Integer(0)).unwrap();
+        let queries = changes.iter().map(|c| c.query.as_str()).collect::<Vec<_>>();
+        assert_eq!(queries, vec!["a.x", "b.x"]);
+        assert_eq!(changes[1].old, Some(Value::Integer(2)));
+
+        let changes = toml.dry_run_delete_all("b.*").unwrap();
+        let queries = changes.iter().map(|c| c.query.as_str()).collect::<Vec<_>>();
+        assert_eq!(queries, vec!["b.x", "b.y"]);
+        assert!(changes.iter().all(|c| c.new.is_none()));
+
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[a]\nx = 1\n[b]\nx = 2\ny = 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dry_run_patch() {
+        let from: Value = toml_from_str("a = 1\nb = 2").unwrap();
+        let to: Value = toml_from_str("a = 3\n[c]\nd = 4").unwrap();
+        let patch = Patch::diff(&from, &to).unwrap();
+
+        let changes = from.dry_run_patch(&patch).unwrap();
+
+        assert_eq!(changes.len(), patch.operations().len());
+        let a = changes.iter().find(|c| c.query == "a").unwrap();
+        assert_eq!(a.old, Some(Value::Integer(1)));
+        assert_eq!(a.new, Some(Value::Integer(3)));
+        let b = changes.iter().find(|c| c.query == "b").unwrap();
+        assert_eq!(b.new, None);
+        assert_eq!(from, toml_from_str::<Value>("a = 1\nb = 2").unwrap());
+    }
+}
@@ -0,0 +1,1054 @@
+//! Format-preserving extensions for toml_edit documents
+//!
+//! The traits in this module work like the ones for `toml::Value`, but on a
+//! `toml_edit::Document` or `toml_edit::Item`. Comments, whitespace and the order of keys and
+//! tables in the document are kept, so only the values which are touched by a query change when
+//! the document is written back to a file.
+//!
+//! This module is only available with the `edit` feature.
+
+use toml_edit::{Array, ArrayOfTables, Document, InlineTable, Item, Table, Value};
+
+use crate::error::{Error, Result};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::tokenizer::Token;
+
+/// How a table is written in a toml_edit document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableLayout {
+    /// A table with a `[header]`
+    #[default]
+    Standard,
+
+    /// Dotted keys in the parent table, like `a.b = 1`
+    Dotted,
+
+    /// An inline table, like `a = { b = 1 }`
+    Inline,
+}
+
+/// How `TomlEditInsertExt::insert_with_layout` writes tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// How tables are written
+    pub tables: TableLayout,
+
+    /// Whether arrays of tables are written as `[[x]]` instead of arrays of inline tables
+    pub array_of_tables: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            tables: TableLayout::Standard,
+            array_of_tables: true,
+        }
+    }
+}
+
+pub trait TomlEditReadExt<'doc> {
+    /// Extension function for reading a value from the current toml_edit document using a
+    /// custom seperator
+    ///
+    /// # Return value
+    ///
+    /// * If the value is found, `Ok(Some(item))` is returned.
+    /// * If a key of the query is not in the document, `Ok(None)` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query is `"a.b.c"` but `"b"` is not a table: error
+    ///     * If the query is `"a.[3]"` but the array at `"a"` has no index `3`: error
+    ///     * etc.
+    ///
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Item>>;
+
+    /// Extension function for reading a value from the current toml_edit document mutably
+    /// using a custom seperator
+    fn read_mut_with_seperator(
+        &'doc mut self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<&'doc mut Item>>;
+
+    /// Extension function for reading a value from the current toml_edit document
+    fn read(&'doc self, query: &str) -> Result<Option<&'doc Item>> {
+        self.read_with_seperator(query, '.')
+    }
+
+    /// Extension function for reading a value from the current toml_edit document mutably
+    fn read_mut(&'doc mut self, query: &str) -> Result<Option<&'doc mut Item>> {
+        self.read_mut_with_seperator(query, '.')
+    }
+}
+
+pub trait TomlEditSetExt {
+    /// Extension function for setting a value in the current toml_edit document using a custom
+    /// seperator
+    ///
+    /// # Semantics
+    ///
+    /// Like `TomlValueSetExt::set_with_seperator`, the function _never_ creates intermediate
+    /// data structures in the document.
+    ///
+    /// If a value replaces a value, the comments and whitespace around the old value are kept.
+    /// Tables which are set inside of inline tables or arrays are converted to inline tables.
+    ///
+    /// # Return value
+    ///
+    /// * If the set operation worked correctly, `Ok(None)` is returned.
+    /// * If the set operation replaced an existing value `Ok(Some(old_value))` is returned
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query is `"a.b.c"` but there is no table `"b"`: error
+    ///     * If the query is `"a.b.[0]"` but "`b"` is not an array: error
+    ///     * If the query is `"a.[0]"`, `"a"` is an array of tables and the value is not a
+    ///       table: error
+    ///     * etc.
+    ///
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Item) -> Result<Option<Item>>;
+
+    /// Extension function for setting a value in the current toml_edit document
+    ///
+    /// See documentation of `TomlEditSetExt::set_with_seperator`
+    fn set(&mut self, query: &str, value: Item) -> Result<Option<Item>> {
+        self.set_with_seperator(query, '.', value)
+    }
+}
+
+pub trait TomlEditInsertExt {
+    /// Extension function for inserting a value in the current toml_edit document using a
+    /// custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// Like `TomlValueInsertExt::insert_with_seperator`, the function creates the missing
+    /// tables and arrays of the query. Tables created inside of a standard table are standard
+    /// tables, which only get a `[header]` if they contain values themselves, and arrays are
+    /// arrays of tables if possible. Tables created inside of an inline table are inline tables.
+    /// See `TomlEditInsertExt::insert_with_layout_and_seperator` for creating them differently.
+    ///
+    /// If a value is inserted into an array, the values after it are shifted. If the index is
+    /// larger than the last index, the value is appended. If the index is not the last one of
+    /// the query, a new table or array is appended instead.
+    ///
+    /// # Return value
+    ///
+    /// * If the insert operation worked correctly, `Ok(None)` is returned.
+    /// * If the insert operation replaced an existing value `Ok(Some(old_value))` is returned
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query is `"a.b.c"` but `"b"` is not a table: error
+    ///     * If the query is `"a.[0]"` but `"a"` is an array of tables and the value is not a
+    ///       table: error
+    ///     * etc.
+    ///
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Item,
+    ) -> Result<Option<Item>>;
+
+    /// Extension function for inserting a value in the current toml_edit document
+    ///
+    /// See documentation of `TomlEditInsertExt::insert_with_seperator`
+    fn insert(&mut self, query: &str, value: Item) -> Result<Option<Item>> {
+        self.insert_with_seperator(query, '.', value)
+    }
+
+    /// Extension function for inserting a value in the current toml_edit document using a
+    /// custom seperator, writing tables with `layout`
+    ///
+    /// # Semantics
+    ///
+    /// Works like `TomlEditInsertExt::insert_with_seperator`, but the tables which are created
+    /// and the tables in `value` are written as `layout.tables` says, and arrays of tables as
+    /// `[[x]]` if `layout.array_of_tables` is true. Inside of inline tables and arrays,
+    /// everything is written inline.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate toml_edit;
+    /// extern crate toml_query;
+    ///
+    /// use toml_query::edit::{Layout, TableLayout, TomlEditInsertExt};
+    ///
+    /// let mut doc : toml_edit::Document = "[package]\n".parse().unwrap();
+    /// let layout = Layout { tables: TableLayout::Dotted, array_of_tables: true };
+    ///
+    /// doc.insert_with_layout("package.metadata.docs.all-features", toml_edit::value(true), layout)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(doc.to_string(), "[package]\nmetadata.docs.all-features = true\n");
+    /// ```
+    ///
+    fn insert_with_layout_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Item,
+        layout: Layout,
+    ) -> Result<Option<Item>>;
+
+    /// Extension function for inserting a value in the current toml_edit document, writing
+    /// tables with `layout`
+    ///
+    /// See documentation of `TomlEditInsertExt::insert_with_layout_and_seperator`
+    fn insert_with_layout(
+        &mut self,
+        query: &str,
+        value: Item,
+        layout: Layout,
+    ) -> Result<Option<Item>> {
+        self.insert_with_layout_and_seperator(query, '.', value, layout)
+    }
+}
+
+pub trait TomlEditDeleteExt {
+    /// Extension function for deleting a value in the current toml_edit document using a custom
+    /// seperator
+    ///
+    /// # Semantics
+    ///
+    /// Like `TomlValueDeleteExt::delete_with_seperator`, the function does _not_ delete
+    /// non-empty tables and arrays. The comments in front of a deleted key are deleted with it.
+    ///
+    /// # Return value
+    ///
+    /// * If the delete operation worked correctly, `Ok(Some(item))` is returned.
+    /// * If there is no value at the query, `Ok(None)` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the value is a non-empty table or array: error
+    ///     * If the query is `"a.[3]"` but the array at `"a"` has no index `3`: error
+    ///     * etc.
+    ///
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Item>>;
+
+    /// Extension function for deleting a value from the current toml_edit document
+    ///
+    /// See documentation of `TomlEditDeleteExt::delete_with_seperator`
+    fn delete(&mut self, query: &str) -> Result<Option<Item>> {
+        self.delete_with_seperator(query, '.')
+    }
+
+    /// Extension function for deleting a value and everything below it in the current
+    /// toml_edit document using a custom seperator
+    ///
+    /// Other than `TomlEditDeleteExt::delete_with_seperator`, non-empty tables and arrays are
+    /// deleted as well, and `Ok(None)` is returned if the index of the query is not in the
+    /// array.
+    fn delete_recursive_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Item>>;
+
+    /// Extension function for deleting a value and everything below it from the current
+    /// toml_edit document
+    ///
+    /// See documentation of `TomlEditDeleteExt::delete_recursive_with_seperator`
+    fn delete_recursive(&mut self, query: &str) -> Result<Option<Item>> {
+        self.delete_recursive_with_seperator(query, '.')
+    }
+}
+
+/// The error for accessing `item` with `token`, if `item` cannot be accessed like that
+pub(crate) fn mismatch(item: &Item, token: &Token) -> Option<Error> {
+    let is_array = item.is_array() || item.is_array_of_tables();
+
+    match *token {
+        Token::Identifier { ref ident, .. } if is_array => {
+            Some(Error::NoIdentifierInArray(ident.clone()))
+        }
+        Token::Identifier { ref ident, .. } if !item.is_table_like() => {
+            Some(Error::QueryingValueAsTable(ident.clone()))
+        }
+        Token::Index { idx, .. } if item.is_table_like() => Some(Error::NoIndexInTable(idx)),
+        Token::Index { idx, .. } if !is_array => Some(Error::QueryingValueAsArray(idx)),
+        _ => None,
+    }
+}
+
+/// The number of values in `item`, if it is an array or an array of tables
+fn len(item: &Item) -> usize {
+    match item {
+        Item::ArrayOfTables(ref aot) => aot.len(),
+        Item::Value(Value::Array(ref array)) => array.len(),
+        _ => 0,
+    }
+}
+
+/// The result of resolving `token` in a structure with `len` values if there is no value
+fn not_found<T>(token: &Token, len: usize, error_if_not_found: bool) -> Result<Option<T>> {
+    match *token {
+        Token::Index { idx, .. } => Err(Error::IndexOutOfBounds(idx, len)),
+        Token::Identifier { ref ident, .. } if error_if_not_found => {
+            Err(Error::IdentifierNotFoundInDocument(ident.clone()))
+        }
+        Token::Identifier { .. } => Ok(None),
+    }
+}
+
+/// Resolves the path in the passed item recursively, like `resolver::non_mut_resolver::resolve`
+pub(crate) fn resolve<'doc>(
+    item: &'doc Item,
+    token: &Token,
+    error_if_not_found: bool,
+) -> Result<Option<&'doc Item>> {
+    if let Some(e) = mismatch(item, token) {
+        return Err(e);
+    }
+
+    let sub = match *token {
+        Token::Identifier { ref ident, .. } => item.get(ident),
+        Token::Index { idx, .. } => item.get(idx),
+    };
+
+    match (sub, token.next()) {
+        (Some(sub), Some(next)) => resolve(sub, next, error_if_not_found),
+        (Some(sub), None) => Ok(Some(sub)),
+        (None, _) => not_found(token, len(item), error_if_not_found),
+    }
+}
+
+/// Resolves the path in the passed item recursively, like `resolver::mut_resolver::resolve`
+pub(crate) fn resolve_mut<'doc>(
+    item: &'doc mut Item,
+    token: &Token,
+    error_if_not_found: bool,
+) -> Result<Option<&'doc mut Item>> {
+    if let Some(e) = mismatch(item, token) {
+        return Err(e);
+    }
+
+    let len = len(item);
+    let sub = match *token {
+        // Not using Item::get_mut(), which inserts missing keys
+        Token::Identifier { ref ident, .. } => {
+            item.as_table_like_mut().and_then(|t| t.get_mut(ident))
+        }
+        Token::Index { idx, .. } => item.get_mut(idx),
+    };
+
+    match (sub, token.next()) {
+        (Some(sub), Some(next)) => resolve_mut(sub, next, error_if_not_found),
+        (Some(sub), None) => Ok(Some(sub)),
+        (None, _) => not_found(token, len, error_if_not_found),
+    }
+}
+
+/// Resolves the path in the passed item recursively, creating missing tables and arrays, like
+/// `resolver::mut_creating_resolver::resolve`
+///
+/// All tokens but the last one are resolved, the item they resolve to is returned with the
+/// last token. `value_is_table` tells whether the value which is put at the last token is a
+/// table, which decides whether an array created for it is an array of tables.
+fn resolve_creating<'doc, 't>(
+    item: &'doc mut Item,
+    token: &'t Token,
+    value_is_table: bool,
+    layout: Layout,
+) -> Result<(&'doc mut Item, &'t Token)> {
+    let next = match token.next() {
+        None => return Ok((item, token)),
+        Some(next) => next,
+    };
+
+    if let Some(e) = mismatch(item, token) {
+        return Err(e);
+    }
+
+    let inline = item.is_value();
+    let sub = match *token {
+        Token::Identifier { ref ident, .. } => {
+            // safe because mismatch() checked that the item is a table
+            let table = item.as_table_like_mut().unwrap();
+            if !table.contains_key(ident) {
+                let array_of_tables = holds_tables(next, value_is_table);
+                table.insert(ident, new_structure(inline, next, array_of_tables, layout));
+            }
+            table.get_mut(ident)
+        }
+        Token::Index { idx, .. } => {
+            let len = len(item);
+            if idx >= len {
+                match item {
+                    Item::ArrayOfTables(ref mut aot) => aot.push(Table::new()),
+                    // safe because new_structure() creates values if inline is true and
+                    // mismatch() checked that the item is an array
+                    _ => {
+                        let element = new_structure(true, next, false, layout);
+                        let element = element.into_value().unwrap();
+                        item.as_array_mut().unwrap().push(element);
+                    }
+                }
+            }
+            item.get_mut(idx.min(len))
+        }
+    };
+
+    // safe because missing tables and array elements were just created
+    resolve_creating(sub.unwrap(), next, value_is_table, layout)
+}
+
+/// Whether the values of an array which is accessed with `index` are tables
+fn holds_tables(index: &Token, value_is_table: bool) -> bool {
+    match index.next() {
+        Some(Token::Identifier { .. }) => true,
+        Some(Token::Index { .. }) => false,
+        None => value_is_table,
+    }
+}
+
+/// An empty structure which can be accessed with `next`
+///
+/// The structure is a value if `inline` is true. Arrays are arrays of tables if they are not
+/// inline, hold tables and `layout` asks for it.
+fn new_structure(inline: bool, next: &Token, holds_tables: bool, layout: Layout) -> Item {
+    match (next, layout.tables) {
+        (Token::Index { .. }, _) if layout.array_of_tables && holds_tables && !inline => {
+            Item::ArrayOfTables(ArrayOfTables::new())
+        }
+        (Token::Index { .. }, _) => Item::Value(Value::Array(Array::new())),
+        (Token::Identifier { .. }, _) if inline => {
+            Item::Value(Value::InlineTable(InlineTable::new()))
+        }
+        (Token::Identifier { .. }, TableLayout::Inline) => {
+            Item::Value(Value::InlineTable(InlineTable::new()))
+        }
+        (Token::Identifier { .. }, TableLayout::Dotted) => {
+            let mut table = Table::new();
+            table.set_dotted(true);
+            Item::Table(table)
+        }
+        (Token::Identifier { .. }, TableLayout::Standard) => {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        }
+    }
+}
+
+/// Write the tables and arrays of tables in `item` with `layout`
+fn apply_layout(item: Item, layout: Layout) -> Item {
+    let item = match item.into_array_of_tables() {
+        Ok(aot) => {
+            let mut tables = ArrayOfTables::new();
+            for table in aot {
+                tables.push(apply_layout_inside(table, layout));
+            }
+
+            return if layout.array_of_tables {
+                Item::ArrayOfTables(tables)
+            } else {
+                Item::Value(Value::Array(tables.into_array()))
+            };
+        }
+        Err(item) => item,
+    };
+
+    match item.into_table() {
+        Ok(table) => {
+            let mut table = apply_layout_inside(table, layout);
+            match layout.tables {
+                TableLayout::Standard => {
+                    // Tables which only contain tables do not need a header
+                    let implicit = table.get_values().is_empty() && !table.is_empty();
+                    table.set_implicit(implicit);
+                    table.set_dotted(false);
+                    Item::Table(table)
+                }
+                TableLayout::Dotted => {
+                    table.set_dotted(true);
+                    Item::Table(table)
+                }
+                TableLayout::Inline => Item::Value(Value::InlineTable(table.into_inline_table())),
+            }
+        }
+        Err(item) => item,
+    }
+}
+
+/// Write the tables and arrays of tables inside of `table` with `layout`
+fn apply_layout_inside(mut table: Table, layout: Layout) -> Table {
+    for (_, item) in table.iter_mut() {
+        *item = apply_layout(std::mem::take(item), layout);
+    }
+    table
+}
+
+/// Convert `item` so that it can be put into `parent`
+///
+/// Inline tables and arrays can only contain values, so tables are converted to inline tables
+/// and arrays of tables to arrays there.
+fn fit(parent: &Item, item: Item) -> Result<Item> {
+    if parent.is_value() {
+        item.into_value()
+            .map(Item::Value)
+            .map_err(|item| Error::TypeError("value", item.type_name()))
+    } else {
+        Ok(item)
+    }
+}
+
+/// Replace `old` with `new`, keeping the comments and whitespace around `old`
+fn replace(old: &mut Item, mut new: Item) -> Item {
+    match (&*old, &mut new) {
+        (Item::Value(ref old), Item::Value(ref mut new)) => {
+            *new.decor_mut() = old.decor().clone();
+        }
+        (Item::Table(ref old), Item::Table(ref mut new)) => {
+            *new.decor_mut() = old.decor().clone();
+            if let Some(position) = old.position() {
+                new.set_position(position);
+            }
+        }
+        _ => {}
+    }
+    std::mem::replace(old, new)
+}
+
+/// Put `value` into `parent` at `token`
+///
+/// Values at a key are replaced. Values at an index are replaced if `shift` is false and
+/// inserted otherwise. If the index is larger than the last index, the value is appended.
+fn put(parent: &mut Item, token: &Token, value: Item, shift: bool) -> Result<Option<Item>> {
+    if let Some(e) = mismatch(parent, token) {
+        return Err(e);
+    }
+
+    let value = fit(parent, value)?;
+    match (parent, token) {
+        (parent, Token::Identifier { ref ident, .. }) => {
+            // safe because mismatch() checked that the item is a table
+            let table = parent.as_table_like_mut().unwrap();
+            match table.get_mut(ident) {
+                Some(old) => Ok(Some(replace(old, value))),
+                None => Ok(table.insert(ident, value)),
+            }
+        }
+
+        (Item::ArrayOfTables(ref mut aot), Token::Index { idx, .. }) => {
+            let table = value
+                .into_table()
+                .map_err(|value| Error::TypeError("table", value.type_name()))?;
+
+            if *idx >= aot.len() {
+                aot.push(table);
+                Ok(None)
+            } else if shift {
+                let mut tables = aot.iter().cloned().collect::<Vec<_>>();
+                tables.insert(*idx, table);
+                aot.clear();
+                tables.into_iter().for_each(|t| aot.push(t));
+                Ok(None)
+            } else {
+                // safe because of the bounds check above
+                let old = aot.get_mut(*idx).unwrap();
+                Ok(Some(Item::Table(std::mem::replace(old, table))))
+            }
+        }
+
+        (parent, Token::Index { idx, .. }) => {
+            // safe because fit() made the value a value and mismatch() checked that the item
+            // is an array
+            let value = value.into_value().unwrap();
+            let array = parent.as_array_mut().unwrap();
+
+            if *idx >= array.len() {
+                array.push(value);
+                Ok(None)
+            } else if shift {
+                array.insert(*idx, value);
+                Ok(None)
+            } else {
+                Ok(Some(Item::Value(array.replace(*idx, value))))
+            }
+        }
+    }
+}
+
+/// Insert `value` at `query`, creating missing tables and arrays with `layout`
+fn insert(
+    item: &mut Item,
+    query: &str,
+    sep: char,
+    value: Item,
+    layout: Layout,
+) -> Result<Option<Item>> {
+    let tokens = tokenize_with_seperator(query, sep)?;
+    let value_is_table = value.is_table() || value.is_inline_table();
+    let (parent, last) = resolve_creating(item, &tokens, value_is_table, layout)?;
+
+    put(parent, last, value, true)
+}
+
+/// Remove the value at `token` from `parent`
+///
+/// Non-empty tables and arrays are only removed if `recursive` is true.
+fn remove(parent: &mut Item, token: &Token, recursive: bool) -> Result<Option<Item>> {
+    if let Some(e) = mismatch(parent, token) {
+        return Err(e);
+    }
+
+    let length = len(parent);
+    let (target, ident) = match *token {
+        Token::Identifier { ref ident, .. } => (parent.get(ident), Some(ident.clone())),
+        Token::Index { idx, .. } => (parent.get(idx), None),
+    };
+
+    match target {
+        None if recursive => return Ok(None),
+        None => {
+            return match *token {
+                Token::Index { idx, .. } => Err(Error::ArrayIndexOutOfBounds(idx, length)),
+                Token::Identifier { .. } => Ok(None),
+            };
+        }
+        Some(_) if recursive => {}
+        Some(target) => {
+            if target.as_table_like().map(|t| t.is_empty()) == Some(false) {
+                return Err(Error::CannotDeleteNonEmptyTable(ident));
+            }
+            if len(target) > 0 {
+                return Err(Error::CannotDeleteNonEmptyArray(ident));
+            }
+        }
+    }
+
+    match (parent, token) {
+        (parent, Token::Identifier { ref ident, .. }) => {
+            // safe because mismatch() checked that the item is a table
+            Ok(parent.as_table_like_mut().unwrap().remove(ident))
+        }
+        (Item::ArrayOfTables(ref mut aot), Token::Index { idx, .. }) => {
+            let table = aot.get(*idx).cloned();
+            aot.remove(*idx);
+            Ok(table.map(Item::Table))
+        }
+        (parent, Token::Index { idx, .. }) => {
+            // safe because mismatch() checked that the item is an array
+            let array = parent.as_array_mut().unwrap();
+            Ok(Some(Item::Value(array.remove(*idx))))
+        }
+    }
+}
+
+impl<'doc> TomlEditReadExt<'doc> for Item {
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Item>> {
+        let tokens = tokenize_with_seperator(query, sep)?;
+        resolve(self, &tokens, false)
+    }
+
+    fn read_mut_with_seperator(
+        &'doc mut self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<&'doc mut Item>> {
+        let tokens = tokenize_with_seperator(query, sep)?;
+        resolve_mut(self, &tokens, false)
+    }
+}
+
+impl TomlEditSetExt for Item {
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Item) -> Result<Option<Item>> {
+        let mut tokens = tokenize_with_seperator(query, sep)?;
+        let (parent, last) = match tokens.pop_last() {
+            None => (self, Box::new(tokens)),
+            // safe because of resolve_mut() guarantees
+            Some(last) => (resolve_mut(self, &tokens, true)?.unwrap(), last),
+        };
+
+        put(parent, &last, value, false)
+    }
+}
+
+impl TomlEditInsertExt for Item {
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Item,
+    ) -> Result<Option<Item>> {
+        insert(self, query, sep, value, Layout::default())
+    }
+
+    fn insert_with_layout_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Item,
+        layout: Layout,
+    ) -> Result<Option<Item>> {
+        insert(self, query, sep, apply_layout(value, layout), layout)
+    }
+}
+
+impl TomlEditDeleteExt for Item {
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Item>> {
+        let mut tokens = tokenize_with_seperator(query, sep)?;
+        let (parent, last) = match tokens.pop_last() {
+            None => (self, Box::new(tokens)),
+            // safe because of resolve_mut() guarantees
+            Some(last) => (resolve_mut(self, &tokens, true)?.unwrap(), last),
+        };
+
+        remove(parent, &last, false)
+    }
+
+    fn delete_recursive_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Item>> {
+        let mut tokens = tokenize_with_seperator(query, sep)?;
+        let (parent, last) = match tokens.pop_last() {
+            None => (self, Box::new(tokens)),
+            // safe because of resolve_mut() guarantees
+            Some(last) => (resolve_mut(self, &tokens, true)?.unwrap(), last),
+        };
+
+        remove(parent, &last, true)
+    }
+}
+
+impl<'doc> TomlEditReadExt<'doc> for Document {
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Item>> {
+        self.as_item().read_with_seperator(query, sep)
+    }
+
+    fn read_mut_with_seperator(
+        &'doc mut self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<&'doc mut Item>> {
+        self.as_item_mut().read_mut_with_seperator(query, sep)
+    }
+}
+
+impl TomlEditSetExt for Document {
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Item) -> Result<Option<Item>> {
+        self.as_item_mut().set_with_seperator(query, sep, value)
+    }
+}
+
+impl TomlEditInsertExt for Document {
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Item,
+    ) -> Result<Option<Item>> {
+        self.as_item_mut().insert_with_seperator(query, sep, value)
+    }
+
+    fn insert_with_layout_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Item,
+        layout: Layout,
+    ) -> Result<Option<Item>> {
+        self.as_item_mut()
+            .insert_with_layout_and_seperator(query, sep, value, layout)
+    }
+}
+
+impl TomlEditDeleteExt for Document {
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Item>> {
+        self.as_item_mut().delete_with_seperator(query, sep)
+    }
+
+    fn delete_recursive_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Item>> {
+        self.as_item_mut()
+            .delete_recursive_with_seperator(query, sep)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml_edit::value;
+
+    const CONFIG: &str = r#"# The server configuration
+[server]
+# Where to listen
+host = "localhost"
+port = 8080 # http
+
+[[clients]]
+name = "a"
+
+[[clients]]
+name = "b"
+"#;
+
+    #[test]
+    fn test_read() {
+        let config = CONFIG.parse::<Document>().unwrap();
+
+        let port = config.read("server.port").unwrap().unwrap();
+        assert_eq!(port.as_integer(), Some(8080));
+
+        let name = config.read("clients.[1].name").unwrap().unwrap();
+        assert_eq!(name.as_str(), Some("b"));
+
+        assert!(config.read("server.missing").unwrap().is_none());
+        assert!(config.read("missing.key").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let config = CONFIG.parse::<Document>().unwrap();
+
+        assert!(is_match!(
+            config.read("server.port.a"),
+            Err(Error::QueryingValueAsTable(_))
+        ));
+        assert!(is_match!(
+            config.read("server.[0]"),
+            Err(Error::NoIndexInTable(0))
+        ));
+        assert!(is_match!(
+            config.read("clients.name"),
+            Err(Error::NoIdentifierInArray(_))
+        ));
+        assert!(is_match!(
+            config.read("clients.[2]"),
+            Err(Error::IndexOutOfBounds(2, 2))
+        ));
+    }
+
+    #[test]
+    fn test_read_inline_table_and_array() {
+        let config: Document = "point = { x = 1, y = [2, 3] }".parse().unwrap();
+
+        let y = config.read("point.y.[1]").unwrap().unwrap();
+        assert_eq!(y.as_integer(), Some(3));
+    }
+
+    #[test]
+    fn test_read_mut() {
+        let mut config: Document = "a = 1 # one".parse().unwrap();
+
+        *config.read_mut("a").unwrap().unwrap() = value(2);
+
+        assert_eq!(config.to_string(), "a = 2\n");
+    }
+
+    #[test]
+    fn test_set_keeps_formatting() {
+        let mut config = CONFIG.parse::<Document>().unwrap();
+
+        let old = config.set("server.port", value(9090)).unwrap();
+
+        assert_eq!(old.unwrap().as_integer(), Some(8080));
+        assert_eq!(config.to_string(), CONFIG.replace("8080", "9090"));
+    }
+
+    #[test]
+    fn test_set_new_key_and_errors() {
+        let mut config = CONFIG.parse::<Document>().unwrap();
+
+        assert!(config.set("server.timeout", value(10)).unwrap().is_none());
+        assert_eq!(
+            config.read("server.timeout").unwrap().unwrap().as_integer(),
+            Some(10)
+        );
+
+        assert!(is_match!(
+            config.set("missing.key", value(1)),
+            Err(Error::IdentifierNotFoundInDocument(_))
+        ));
+        assert!(is_match!(
+            config.set("clients.[0]", value(1)),
+            Err(Error::TypeError("table", _))
+        ));
+    }
+
+    #[test]
+    fn test_set_in_inline_table_and_array() {
+        let mut config: Document = "point = { x = 1, y = 2 }\nlist = [1, 2, 3]\n"
+            .parse()
+            .unwrap();
+
+        config.set("point.y", value(5)).unwrap();
+        config.set("list.[1]", value(7)).unwrap();
+        config.set("list.[5]", value(9)).unwrap();
+
+        assert_eq!(
+            config.to_string(),
+            "point = { x = 1, y = 5 }\nlist = [1, 7, 3, 9]\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_creates_tables() {
+        let mut config: Document = "# Settings\n[a]\nx = 1\n".parse().unwrap();
+
+        config.insert("a.b.c", value(2)).unwrap();
+        config.insert("point.x", value(1)).unwrap();
+
+        assert_eq!(config.read("a.b.c").unwrap().unwrap().as_integer(), Some(2));
+        let expected = "# Settings\n[a]\nx = 1\n\n[a.b]\nc = 2\n\n[point]\nx = 1\n";
+        assert_eq!(config.to_string(), expected);
+    }
+
+    #[test]
+    fn test_insert_into_inline_table_and_arrays() {
+        let mut config: Document = "point = { x = 1 }\nlist = [1, 3]\n[[t]]\nn = 1\n"
+            .parse()
+            .unwrap();
+
+        config.insert("point.y.z", value(2)).unwrap();
+        config.insert("list.[1]", value(2)).unwrap();
+
+        let mut table = Table::new();
+        table.insert("n", value(0));
+        config.insert("t.[0]", Item::Table(table)).unwrap();
+
+        assert_eq!(
+            config.read("point.y.z").unwrap().unwrap().as_integer(),
+            Some(2)
+        );
+        assert_eq!(
+            config.read("t.[0].n").unwrap().unwrap().as_integer(),
+            Some(0)
+        );
+        assert!(config.read("t.[1]").unwrap().is_some());
+        assert_eq!(config.to_string().lines().nth(1), Some("list = [1, 2, 3]"));
+    }
+
+    #[test]
+    fn test_insert_keeps_inline_table_value() {
+        let mut config: Document = "".parse().unwrap();
+
+        let point = "{ x = 1 }".parse::<Value>().unwrap();
+        config.insert("point", Item::Value(point)).unwrap();
+
+        assert_eq!(config.to_string(), "point = { x = 1 }\n");
+    }
+
+    #[test]
+    fn test_insert_creates_arrays() {
+        let mut config: Document = "".parse().unwrap();
+
+        config.insert("servers.[0].name", value("a")).unwrap();
+        config.insert("servers.[5].name", value("b")).unwrap();
+        config.insert("point.list.[0]", value(1)).unwrap();
+
+        assert_eq!(
+            config.read("servers.[1].name").unwrap().unwrap().as_str(),
+            Some("b")
+        );
+        let expected =
+            "[[servers]]\nname = \"a\"\n\n[[servers]]\nname = \"b\"\n\n[point]\nlist = [1]\n";
+        assert_eq!(config.to_string(), expected);
+    }
+
+    #[test]
+    fn test_insert_with_layout() {
+        let standard = Layout::default();
+        let dotted = Layout {
+            tables: TableLayout::Dotted,
+            ..Layout::default()
+        };
+        let inline = Layout {
+            tables: TableLayout::Inline,
+            array_of_tables: false,
+        };
+
+        let mut config: Document = "name = \"x\"\n".parse().unwrap();
+        config
+            .insert_with_layout("a.b.c", value(1), dotted)
+            .unwrap();
+        config.insert_with_layout("d.e", value(2), inline).unwrap();
+        config
+            .insert_with_layout("f.[0].g", value(3), inline)
+            .unwrap();
+        config
+            .insert_with_layout("h.i", value(4), standard)
+            .unwrap();
+        config
+            .insert_with_layout("j.[0].k", value(5), standard)
+            .unwrap();
+
+        let expected = r#"name = "x"
+a.b.c = 1
+d = { e = 2 }
+f = [{ g = 3 }]
+
+[h]
+i = 4
+
+[[j]]
+k = 5
+"#;
+        assert_eq!(config.to_string(), expected);
+    }
+
+    #[test]
+    fn test_insert_with_layout_converts_value() {
+        let inline = Layout {
+            tables: TableLayout::Inline,
+            array_of_tables: false,
+        };
+
+        let mut table = Table::new();
+        table.insert("x", value(1));
+        table.insert("sub", Item::Table(Table::new()));
+        let servers = "[{ name = \"a\" }, { name = \"b\" }]"
+            .parse::<Value>()
+            .unwrap();
+
+        let mut config: Document = "".parse().unwrap();
+        config
+            .insert_with_layout("inline", Item::Table(table.clone()), inline)
+            .unwrap();
+        config
+            .insert_with_layout("servers", Item::Value(servers), Layout::default())
+            .unwrap();
+        config
+            .insert_with_layout("standard", Item::Table(table), Layout::default())
+            .unwrap();
+
+        let expected = r#"inline = { x = 1, sub = {} }
+
+[[servers]]
+name = "a"
+
+[[servers]]
+name = "b"
+
+[standard]
+x = 1
+
+[standard.sub]
+"#;
+        assert_eq!(config.to_string(), expected);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut config = CONFIG.parse::<Document>().unwrap();
+
+        let old = config.delete("server.host").unwrap();
+        assert_eq!(old.unwrap().as_str(), Some("localhost"));
+        assert!(config.delete("server.missing").unwrap().is_none());
+
+        assert!(is_match!(
+            config.delete("server"),
+            Err(Error::CannotDeleteNonEmptyTable(Some(_)))
+        ));
+        assert!(is_match!(
+            config.delete("clients"),
+            Err(Error::CannotDeleteNonEmptyArray(Some(_)))
+        ));
+
+        assert_eq!(
+            config.to_string(),
+            CONFIG.replace("# Where to listen\nhost = \"localhost\"\n", "")
+        );
+    }
+
+    #[test]
+    fn test_delete_recursive() {
+        let mut config = CONFIG.parse::<Document>().unwrap();
+
+        assert!(config.delete_recursive("clients.[0]").unwrap().is_some());
+        assert!(config.delete_recursive("clients.[5]").unwrap().is_none());
+        assert!(config.delete_recursive("server").unwrap().is_some());
+
+        assert_eq!(config.to_string(), "\n[[clients]]\nname = \"b\"\n");
+    }
+}
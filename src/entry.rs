@@ -0,0 +1,293 @@
+//! The Toml Entry extensions
+
+use toml::value::{Table, Value};
+
+use crate::error::{Error, Result};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::tokenizer::Token;
+
+/// A value in a toml::Value document, which may or may not exist, see `TomlValueEntryExt::entry`
+#[derive(Debug)]
+pub enum Entry<'doc> {
+    /// There is a value at the query
+    Occupied(OccupiedEntry<'doc>),
+
+    /// There is no value at the query
+    Vacant(VacantEntry<'doc>),
+}
+
+impl<'doc> Entry<'doc> {
+    /// Insert `default` if the entry is vacant and get the value
+    pub fn or_insert(self, default: Value) -> &'doc mut Value {
+        self.or_insert_with(|| default)
+    }
+
+    /// Insert the value returned by `default` if the entry is vacant and get the value
+    pub fn or_insert_with<F: FnOnce() -> Value>(self, default: F) -> &'doc mut Value {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modify the value with `f` if the entry is occupied
+    pub fn and_modify<F: FnOnce(&mut Value)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+/// An existing value in a toml::Value document
+#[derive(Debug)]
+pub struct OccupiedEntry<'doc> {
+    value: &'doc mut Value,
+}
+
+impl<'doc> OccupiedEntry<'doc> {
+    /// Get the value
+    pub fn get(&self) -> &Value {
+        self.value
+    }
+
+    /// Get the value mutably
+    pub fn get_mut(&mut self) -> &mut Value {
+        self.value
+    }
+
+    /// Get the value mutably, with the lifetime of the document
+    pub fn into_mut(self) -> &'doc mut Value {
+        self.value
+    }
+
+    /// Replace the value, returning the old one
+    pub fn insert(&mut self, value: Value) -> Value {
+        std::mem::replace(self.value, value)
+    }
+}
+
+/// A missing value in a toml::Value document
+#[derive(Debug)]
+pub struct VacantEntry<'doc> {
+    /// The last table along the query which exists
+    table: &'doc mut Table,
+
+    /// The keys of the missing tables between `table` and the value
+    missing: Vec<String>,
+
+    /// The key of the value
+    key: String,
+}
+
+impl<'doc> VacantEntry<'doc> {
+    /// Collect the missing keys of the query, starting with `ident` in `table`
+    fn new(table: &'doc mut Table, ident: &str, next: Option<&Token>) -> Result<Self> {
+        let mut key = ident.to_owned();
+        let mut missing = vec![];
+
+        let mut current = next;
+        while let Some(token) = current {
+            match token {
+                Token::Identifier { ref ident, .. } => {
+                    missing.push(std::mem::replace(&mut key, ident.clone()))
+                }
+                // Only tables are created
+                Token::Index { idx, .. } => return Err(Error::NoIndexInTable(*idx)),
+            }
+            current = token.next();
+        }
+
+        Ok(VacantEntry {
+            table,
+            missing,
+            key,
+        })
+    }
+
+    /// Insert the value, creating missing intermediate tables, and get it
+    pub fn insert(self, value: Value) -> &'doc mut Value {
+        let mut table = self.table;
+        for name in self.missing {
+            table = match table
+                .entry(name)
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(ref mut t) => t,
+                _ => unreachable!("Missing tables are created as tables"),
+            };
+        }
+
+        table.entry(self.key).or_insert(value)
+    }
+}
+
+pub trait TomlValueEntryExt {
+    /// Extension function for getting the entry of a query in the current toml::Value document
+    /// using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// The entry is `Entry::Occupied` if there is a value at the query and `Entry::Vacant` if
+    /// the last key of the query or one of the tables leading to it does not exist. Inserting
+    /// into a vacant entry creates the missing tables, like `TomlValueInsertExt::insert` does.
+    ///
+    /// # Return value
+    ///
+    /// * If the query could be resolved, `Ok(entry)` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query is `"a.b.c"` but `"b"` is not a table: error
+    ///     * If the query is `"a.[3]"` but the array at `"a"` has no index `3`: error
+    ///     * If the query is `"a.b.[0]"` and there is no array `"b"`, because only tables are
+    ///       created: error
+    ///     * etc.
+    ///
+    fn entry_with_seperator(&mut self, query: &str, sep: char) -> Result<Entry<'_>>;
+
+    /// Extension function for getting the entry of a query in the current toml::Value document
+    ///
+    /// See documentation of `TomlValueEntryExt::entry_with_seperator`
+    fn entry(&mut self, query: &str) -> Result<Entry<'_>> {
+        self.entry_with_seperator(query, '.')
+    }
+}
+
+impl TomlValueEntryExt for Value {
+    fn entry_with_seperator(&mut self, query: &str, sep: char) -> Result<Entry<'_>> {
+        let tokens = tokenize_with_seperator(query, sep)?;
+
+        // Walk down the query once, until the value or the first missing key is found
+        let mut value = self;
+        let mut token = &tokens;
+        loop {
+            value = match (value, token) {
+                (Value::Table(ref mut t), Token::Identifier { ref ident, .. }) => {
+                    if !t.contains_key(ident) {
+                        return VacantEntry::new(t, ident, token.next()).map(Entry::Vacant);
+                    }
+                    &mut t[ident.as_str()]
+                }
+                (Value::Table(_), Token::Index { idx, .. }) => {
+                    return Err(Error::NoIndexInTable(*idx))
+                }
+                (Value::Array(ref mut a), Token::Index { idx, .. }) => {
+                    let len = a.len();
+                    a.get_mut(*idx).ok_or(Error::IndexOutOfBounds(*idx, len))?
+                }
+                (Value::Array(_), Token::Identifier { ref ident, .. }) => {
+                    return Err(Error::NoIdentifierInArray(ident.clone()))
+                }
+                (_, Token::Identifier { ref ident, .. }) => {
+                    return Err(Error::QueryingValueAsTable(ident.clone()))
+                }
+                (_, Token::Index { idx, .. }) => return Err(Error::QueryingValueAsArray(*idx)),
+            };
+
+            match token.next() {
+                Some(next) => token = next,
+                None => return Ok(Entry::Occupied(OccupiedEntry { value })),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_entry_or_insert_creates_tables() {
+        let mut toml: Value = toml_from_str("[a]\nx = 1").unwrap();
+
+        let value = toml.entry("a.b.c").unwrap().or_insert(Value::Integer(0));
+        assert_eq!(value, &Value::Integer(0));
+        *value = Value::Integer(1);
+
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[a]\nx = 1\n[a.b]\nc = 1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_entry_or_insert_keeps_existing_value() {
+        let mut toml: Value = toml_from_str("[a]\nb = 2").unwrap();
+
+        let value = toml.entry("a.b").unwrap().or_insert(Value::Integer(0));
+
+        assert_eq!(value, &Value::Integer(2));
+    }
+
+    #[test]
+    fn test_entry_or_insert_with_top_level_key() {
+        let mut toml: Value = toml_from_str("").unwrap();
+
+        toml.entry("a")
+            .unwrap()
+            .or_insert_with(|| Value::String(String::from("x")));
+
+        assert_eq!(toml, toml_from_str::<Value>("a = \"x\"").unwrap());
+    }
+
+    #[test]
+    fn test_entry_and_modify() {
+        let mut toml: Value = toml_from_str("counter = 1").unwrap();
+
+        for _ in 0..2 {
+            toml.entry("counter")
+                .unwrap()
+                .and_modify(|v| {
+                    if let Value::Integer(ref mut i) = v {
+                        *i += 1
+                    }
+                })
+                .or_insert(Value::Integer(0));
+        }
+        toml.entry("other")
+            .unwrap()
+            .and_modify(|_| panic!("Must not be called"))
+            .or_insert(Value::Integer(0));
+
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("counter = 3\nother = 0").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_entry_in_array() {
+        let mut toml: Value = toml_from_str("a = [{ b = 1 }]").unwrap();
+
+        match toml.entry("a.[0].b").unwrap() {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.insert(Value::Integer(2)), Value::Integer(1));
+            }
+            Entry::Vacant(_) => panic!("Expected an occupied entry"),
+        }
+        assert!(is_match!(toml.entry("a.[0].c").unwrap(), Entry::Vacant(_)));
+
+        assert_eq!(toml, toml_from_str::<Value>("a = [{ b = 2 }]").unwrap());
+    }
+
+    #[test]
+    fn test_entry_errors() {
+        let mut toml: Value = toml_from_str("a = 1\nb = [1]").unwrap();
+
+        assert!(is_match!(
+            toml.entry("a.b"),
+            Err(Error::QueryingValueAsTable(_))
+        ));
+        assert!(is_match!(
+            toml.entry("b.[1]"),
+            Err(Error::IndexOutOfBounds(1, 1))
+        ));
+        assert!(is_match!(
+            toml.entry("c.[0]"),
+            Err(Error::NoIndexInTable(0))
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-/// Error types
+//! Error types
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -66,4 +66,73 @@ pub enum Error {
 
     #[fail(display = "Value at '{}' not there", _0)]
     NotAvailable(String),
+
+    #[fail(display = "Cannot replace the {} at '{}' with a {}", _1, _0, _2)]
+    CannotChangeType(String, &'static str, &'static str),
+
+    // Errors for Patch
+    #[fail(display = "Cannot patch a {} into a {} at the document root", _0, _1)]
+    CannotPatchRoot(&'static str, &'static str),
+
+    #[fail(display = "The key '{}' cannot be queried with seperator '{}'", _0, _1)]
+    KeyNotQueryable(String, char),
+
+    #[fail(display = "Cannot move '{}' into itself at '{}'", _0, _1)]
+    CannotMoveIntoItself(String, String),
+
+    #[fail(display = "Cannot rename the index {}, only keys can be renamed", _0)]
+    CannotRenameIndex(usize),
+
+    // Errors for Transaction
+    #[fail(
+        display = "Operation {} ('{}') of the transaction failed: {}",
+        _0, _1, _2
+    )]
+    TransactionFailed(usize, String, Box<Error>),
+
+    // Errors for EditHistory
+    #[fail(display = "The checkpoint is not part of the history")]
+    UnknownCheckpoint,
+
+    // Errors for Merge
+    #[fail(display = "Cannot merge a {} and a {} at the document root", _0, _1)]
+    CannotMergeRoot(&'static str, &'static str),
+
+    #[fail(
+        display = "Cannot add the conflict table to a {} at the document root",
+        _0
+    )]
+    CannotAddConflictTable(&'static str),
+
+    // Errors for Array
+    #[fail(display = "The value at '{}' is a {}, not an Array", _0, _1)]
+    NotAnArray(String, &'static str),
+
+    #[fail(display = "Cannot sort by '{}', cannot compare {} with {}", _0, _1, _2)]
+    CannotCompare(String, &'static str, &'static str),
+
+    // Errors for GuardedDocument
+    #[fail(display = "Access to '{}' is denied by the rule '{}'", _0, _1)]
+    PermissionDenied(String, String),
+
+    // Errors for comments
+    #[cfg(feature = "edit")]
+    #[fail(display = "Cannot attach a comment to '{}'", _0)]
+    CannotComment(String),
+
+    #[cfg(feature = "edit")]
+    #[fail(display = "The trailing comment of '{}' must be a single line", _0)]
+    MultiLineComment(String),
+
+    // Errors for reading Partials
+    #[cfg(feature = "typed")]
+    #[fail(display = "The keys {:?} are not used", _0)]
+    UnusedKeys(Vec<String>),
+
+    // Errors for Validated
+    #[fail(
+        display = "The array at '{}' cannot contain both {} and {}",
+        _0, _1, _2
+    )]
+    HeterogeneousArray(String, &'static str, &'static str),
 }
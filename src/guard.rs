@@ -0,0 +1,525 @@
+//! Enforcing access rules on a toml::Value document
+
+use toml::Value;
+
+use crate::delete::TomlValueDeleteExt;
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::read::TomlValueReadExt;
+use crate::resolver::multi_resolver::{matches, resolve};
+use crate::set::{SetOptions, TomlValueSetExt};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::tokenizer::Token;
+use crate::util::{get_path, token_path, Segment};
+
+/// A pattern of a `Policy`
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: String,
+    tokens: Token,
+}
+
+impl Rule {
+    /// Check whether accessing `path` in `document` touches a value matching this rule
+    ///
+    /// This is the case if the value at `path` or one of the values it is inside of matches, if
+    /// a value inside of it matches, or if a value inside of `value`, which is written to `path`,
+    /// would match.
+    fn covers(&self, document: &Value, path: &[Segment], value: Option<&Value>) -> bool {
+        (1..=path.len()).any(|i| matches(&self.tokens, &path[..i]))
+            || resolve(document, &self.tokens)
+                .iter()
+                .any(|m| m.starts_with(path))
+            || value
+                .into_iter()
+                .any(|v| self.covers_value(&mut path.to_vec(), v))
+    }
+
+    fn covers_value(&self, path: &mut Vec<Segment>, value: &Value) -> bool {
+        let children: Vec<(Segment, &Value)> = match value {
+            Value::Table(ref t) => t
+                .iter()
+                .map(|(k, v)| (Segment::Key(k.clone()), v))
+                .collect(),
+            Value::Array(ref a) => a
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (Segment::Index(i), v))
+                .collect(),
+            _ => vec![],
+        };
+
+        children.into_iter().any(|(segment, child)| {
+            path.push(segment);
+            let covered = matches(&self.tokens, path) || self.covers_value(path, child);
+            path.pop();
+            covered
+        })
+    }
+}
+
+/// Rules restricting the access to a document, see `GuardedDocument`
+///
+/// The rules are query patterns, which may contain the wildcards `*` and `**`, see
+/// `TomlValueSetExt::set_all_with_seperator`.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    read_only: Vec<Rule>,
+    hidden: Vec<Rule>,
+}
+
+impl Policy {
+    /// A policy without rules, which allows everything
+    pub fn new() -> Policy {
+        Policy::default()
+    }
+
+    /// Add a pattern for values which can be read, but not modified
+    pub fn read_only(self, pattern: &str) -> Result<Policy> {
+        self.read_only_with_seperator(pattern, '.')
+    }
+
+    /// Add a pattern for values which can be read, but not modified, using a custom seperator
+    pub fn read_only_with_seperator(mut self, pattern: &str, sep: char) -> Result<Policy> {
+        self.read_only.push(Rule {
+            pattern: pattern.to_owned(),
+            tokens: tokenize_with_seperator(pattern, sep)?,
+        });
+        Ok(self)
+    }
+
+    /// Add a pattern for values which can neither be read nor modified
+    pub fn hidden(self, pattern: &str) -> Result<Policy> {
+        self.hidden_with_seperator(pattern, '.')
+    }
+
+    /// Add a pattern for values which can neither be read nor modified, using a custom
+    /// seperator
+    pub fn hidden_with_seperator(mut self, pattern: &str, sep: char) -> Result<Policy> {
+        self.hidden.push(Rule {
+            pattern: pattern.to_owned(),
+            tokens: tokenize_with_seperator(pattern, sep)?,
+        });
+        Ok(self)
+    }
+}
+
+/// A document which can only be accessed as a `Policy` allows
+///
+/// The `GuardedDocument` implements the read, set, insert and delete extension traits. An
+/// operation fails with `Error::PermissionDenied`, naming the query and the pattern of the rule,
+/// if it would
+///
+/// * read a hidden value, or a table or array containing one, or
+/// * modify a read-only or hidden value, a table or array containing one, or a value inside of
+///   one, or
+/// * create a value matching a read-only or hidden pattern.
+///
+/// Getting a value mutably counts as modifying it. Inserting or deleting an array element shifts
+/// the elements after it, so it counts as modifying all of them.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate toml;
+/// extern crate toml_query;
+///
+/// use toml_query::error::Error;
+/// use toml_query::guard::{GuardedDocument, Policy};
+/// use toml_query::read::TomlValueReadExt;
+/// use toml_query::set::TomlValueSetExt;
+///
+/// let mut toml : toml::Value = toml::from_str("[security]\nlevel = 1").unwrap();
+/// let policy = Policy::new().read_only("security.*").unwrap();
+/// let mut guarded = GuardedDocument::new(&mut toml, policy);
+///
+/// assert!(guarded.read("security.level").unwrap().is_some());
+///
+/// match guarded.set("security.level", toml::Value::Integer(0)) {
+///     Err(Error::PermissionDenied(query, pattern)) => {
+///         assert_eq!(query, "security.level");
+///         assert_eq!(pattern, "security.*");
+///     }
+///     _ => panic!("Expected the set operation to be denied"),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct GuardedDocument<'doc> {
+    document: &'doc mut Value,
+    policy: Policy,
+}
+
+impl<'doc> GuardedDocument<'doc> {
+    /// Guard `document` with `policy`
+    pub fn new(document: &'doc mut Value, policy: Policy) -> GuardedDocument<'doc> {
+        GuardedDocument { document, policy }
+    }
+
+    /// The policy of this document
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    fn check<'r, I>(&self, rules: I, query: &str, sep: char, value: Option<&Value>) -> Result<()>
+    where
+        I: Iterator<Item = &'r Rule>,
+    {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        self.check_path(rules, query, &path, value)
+    }
+
+    fn check_path<'r, I>(
+        &self,
+        mut rules: I,
+        query: &str,
+        path: &[Segment],
+        value: Option<&Value>,
+    ) -> Result<()>
+    where
+        I: Iterator<Item = &'r Rule>,
+    {
+        match rules.find(|rule| rule.covers(self.document, path, value)) {
+            Some(rule) => Err(Error::PermissionDenied(
+                query.to_owned(),
+                rule.pattern.clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn check_read(&self, query: &str, sep: char) -> Result<()> {
+        self.check(self.policy.hidden.iter(), query, sep, None)
+    }
+
+    fn check_write(&self, query: &str, sep: char, value: Option<&Value>) -> Result<()> {
+        let rules = self
+            .policy
+            .hidden
+            .iter()
+            .chain(self.policy.read_only.iter());
+        self.check(rules, query, sep, value)
+    }
+
+    /// Check inserting (`added` is 1) or deleting (`added` is 0) the value at `query`
+    fn check_shifting_write(
+        &self,
+        query: &str,
+        sep: char,
+        value: Option<&Value>,
+        added: usize,
+    ) -> Result<()> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        self.check_shifting_path(query, &path, value, added)
+    }
+
+    /// Check inserting or deleting the value at `path`
+    ///
+    /// If `path` is an index of an array, all elements after it are shifted, so every index
+    /// from it to the end of the array gets another value and is checked as well.
+    fn check_shifting_path(
+        &self,
+        query: &str,
+        path: &[Segment],
+        value: Option<&Value>,
+        added: usize,
+    ) -> Result<()> {
+        let rules = || {
+            self.policy
+                .hidden
+                .iter()
+                .chain(self.policy.read_only.iter())
+        };
+        self.check_path(rules(), query, path, value)?;
+
+        if let Some((Segment::Index(idx), parent)) = path.split_last() {
+            if let Some(Value::Array(ref array)) = get_path(self.document, parent) {
+                let mut shifted = path.to_vec();
+                for i in (idx + 1)..(array.len() + added) {
+                    shifted[parent.len()] = Segment::Index(i);
+                    self.check_path(rules(), query, &shifted, None)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check modifying all values matching `pattern`
+    fn check_write_all(&self, pattern: &str, sep: char, value: Option<&Value>) -> Result<()> {
+        let tokens = tokenize_with_seperator(pattern, sep)?;
+        for path in resolve(self.document, &tokens) {
+            let rules = self
+                .policy
+                .hidden
+                .iter()
+                .chain(self.policy.read_only.iter());
+            self.check_path(rules, pattern, &path, value)?;
+        }
+        Ok(())
+    }
+
+    /// Check deleting all values matching `pattern`
+    fn check_delete_all(&self, pattern: &str, sep: char) -> Result<()> {
+        let tokens = tokenize_with_seperator(pattern, sep)?;
+        for path in resolve(self.document, &tokens) {
+            self.check_shifting_path(pattern, &path, None, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'doc, 'g> TomlValueReadExt<'doc> for GuardedDocument<'g> {
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        self.check_read(query, sep)?;
+        self.document.read_with_seperator(query, sep)
+    }
+
+    fn read_mut_with_seperator(
+        &'doc mut self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<&'doc mut Value>> {
+        self.check_write(query, sep, None)?;
+        self.document.read_mut_with_seperator(query, sep)
+    }
+}
+
+impl<'doc> TomlValueSetExt for GuardedDocument<'doc> {
+    fn set_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        self.check_write(query, sep, Some(&value))?;
+        self.document.set_with_seperator(query, sep, value)
+    }
+
+    fn set_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<(String, Value)>> {
+        self.check_write_all(pattern, sep, Some(&value))?;
+        self.document.set_all_with_seperator(pattern, sep, value)
+    }
+
+    fn set_with_options_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>> {
+        self.check_write(query, sep, Some(&value))?;
+        self.document
+            .set_with_options_and_seperator(query, sep, value, options)
+    }
+}
+
+impl<'doc> TomlValueInsertExt for GuardedDocument<'doc> {
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        self.check_shifting_write(query, sep, Some(&value), 1)?;
+        self.document.insert_with_seperator(query, sep, value)
+    }
+}
+
+impl<'doc> TomlValueDeleteExt for GuardedDocument<'doc> {
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        self.check_shifting_write(query, sep, None, 0)?;
+        self.document.delete_with_seperator(query, sep)
+    }
+
+    fn delete_recursive_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        prune: bool,
+    ) -> Result<Option<Value>> {
+        // Pruned tables contain the deleted value, so they are checked with it
+        self.check_shifting_write(query, sep, None, 0)?;
+        self.document
+            .delete_recursive_with_seperator(query, sep, prune)
+    }
+
+    fn delete_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+    ) -> Result<Vec<(String, Value)>> {
+        self.check_delete_all(pattern, sep)?;
+        self.document.delete_all_with_seperator(pattern, sep)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn policy() -> Policy {
+        Policy::new()
+            .read_only("security.*")
+            .unwrap()
+            .hidden("license.key")
+            .unwrap()
+    }
+
+    fn assert_denied<T: std::fmt::Debug>(res: Result<T>, rule: &str) {
+        match res {
+            Err(Error::PermissionDenied(_, ref pattern)) => assert_eq!(pattern, rule),
+            _ => panic!("Expected permission to be denied, got {:?}", res),
+        }
+    }
+
+    const DOCUMENT: &str = r#"
+        name = "app"
+
+        [security]
+        level = 1
+
+        [license]
+        key = "secret"
+        owner = "me"
+    "#;
+
+    #[test]
+    fn test_read() {
+        let mut toml: Value = toml_from_str(DOCUMENT).unwrap();
+        let guarded = GuardedDocument::new(&mut toml, policy());
+
+        assert!(guarded.read("security.level").unwrap().is_some());
+        assert!(guarded.read("license.owner").unwrap().is_some());
+        assert_denied(guarded.read("license.key"), "license.key");
+        assert_denied(guarded.read("license"), "license.key");
+    }
+
+    #[test]
+    fn test_read_mut_counts_as_modification() {
+        let mut toml: Value = toml_from_str(DOCUMENT).unwrap();
+        let mut guarded = GuardedDocument::new(&mut toml, policy());
+
+        assert_denied(guarded.read_mut("security.level"), "security.*");
+        assert!(guarded.read_mut("name").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_set_and_insert() {
+        let mut toml: Value = toml_from_str(DOCUMENT).unwrap();
+        let mut guarded = GuardedDocument::new(&mut toml, policy());
+
+        assert_denied(
+            guarded.set("security.level", Value::Integer(0)),
+            "security.*",
+        );
+        assert_denied(guarded.set("security", Value::Integer(0)), "security.*");
+        assert_denied(
+            guarded.insert("security.new", Value::Integer(0)),
+            "security.*",
+        );
+        assert_denied(guarded.set("license.key", Value::Integer(0)), "license.key");
+        assert_denied(
+            guarded.insert(
+                "license",
+                toml_from_str::<Value>("key = \"forged\"").unwrap(),
+            ),
+            "license.key",
+        );
+
+        guarded.set("license.owner", Value::Integer(0)).unwrap();
+        guarded.insert("other.value", Value::Integer(0)).unwrap();
+    }
+
+    #[test]
+    fn test_creating_protected_values() {
+        let mut toml: Value = toml_from_str("").unwrap();
+        let mut guarded = GuardedDocument::new(&mut toml, policy());
+
+        assert_denied(
+            guarded.insert("security.level", Value::Integer(0)),
+            "security.*",
+        );
+        assert_denied(
+            guarded.insert("security", toml_from_str::<Value>("level = 0").unwrap()),
+            "security.*",
+        );
+        guarded
+            .insert("security", toml_from_str::<Value>("").unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut toml: Value = toml_from_str(DOCUMENT).unwrap();
+        let mut guarded = GuardedDocument::new(&mut toml, policy());
+
+        assert_denied(guarded.delete("security.level"), "security.*");
+        assert_denied(guarded.delete_recursive("license"), "license.key");
+        assert_denied(guarded.delete_all("*.*"), "license.key");
+        assert_denied(guarded.delete_all("security.*"), "security.*");
+
+        guarded.delete("license.owner").unwrap();
+        guarded.delete_all("name").unwrap();
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[security]\nlevel = 1\n[license]\nkey = \"secret\"").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_shifting_protected_array_elements() {
+        let mut toml: Value =
+            toml_from_str("servers = [{ name = \"a\" }, { password = \"x\" }, { name = \"c\" }]")
+                .unwrap();
+        let policy = Policy::new().hidden("servers.[1].password").unwrap();
+        let mut guarded = GuardedDocument::new(&mut toml, policy);
+
+        // Deleting or inserting before the element would move it to another index
+        assert_denied(
+            guarded.delete_recursive("servers.[0]"),
+            "servers.[1].password",
+        );
+        assert_denied(guarded.delete_all("servers.[0]"), "servers.[1].password");
+        assert_denied(
+            guarded.insert("servers.[0]", Value::Integer(0)),
+            "servers.[1].password",
+        );
+        assert_denied(guarded.read("servers.[1].password"), "servers.[1].password");
+        assert!(guarded.read("servers.[0].password").unwrap().is_none());
+
+        guarded.delete_recursive("servers.[2]").unwrap();
+        guarded
+            .insert(
+                "servers.[2]",
+                toml_from_str::<Value>("name = \"d\"").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>(
+                "servers = [{ name = \"a\" }, { password = \"x\" }, { name = \"d\" }]"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_recursive_wildcard_rule() {
+        let mut toml: Value = toml_from_str("[a]\npassword = \"x\"\n[b]\nc = 1").unwrap();
+        let policy = Policy::new().hidden("**.password").unwrap();
+        let mut guarded = GuardedDocument::new(&mut toml, policy);
+
+        assert_denied(guarded.read("a"), "**.password");
+        assert!(guarded.read("b").unwrap().is_some());
+        assert_denied(
+            guarded.insert("b.password", Value::Integer(1)),
+            "**.password",
+        );
+        guarded.set_all("*.c", Value::Integer(2)).unwrap();
+    }
+}
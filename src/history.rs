@@ -0,0 +1,865 @@
+//! Undo and redo for modifications on a toml::Value document
+
+use toml::Value;
+
+use crate::delete::TomlValueDeleteExt;
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::read::TomlValueReadExt;
+use crate::restructure::TomlValueRestructureExt;
+use crate::set::{SetOptions, TomlValueSetExt};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::update::TomlValueUpdateExt;
+use crate::util::{get_path, get_path_mut, insert_path, remove_path, token_path, Segment};
+
+/// A structural change at an exact path in the document
+///
+/// Applying a change returns the change which reverts it.
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    /// Replace the existing value at the path
+    Replace { path: Vec<Segment>, value: Value },
+
+    /// Insert a value at the path, shifting array elements
+    Insert { path: Vec<Segment>, value: Value },
+
+    /// Remove the value at the path, even if it is a non-empty structure
+    Remove { path: Vec<Segment> },
+
+    /// Apply several changes in order
+    Batch(Vec<Change>),
+}
+
+impl Change {
+    fn apply(self, doc: &mut Value) -> Result<Change> {
+        match self {
+            Change::Replace { path, value } => {
+                let target = get_path_mut(doc, &path).ok_or_else(|| not_available(&path))?;
+                let value = std::mem::replace(target, value);
+                Ok(Change::Replace { path, value })
+            }
+
+            Change::Insert { path, value } => {
+                insert_path(doc, &path, value).map_err(|_| not_available(&path))?;
+                Ok(Change::Remove { path })
+            }
+
+            Change::Remove { path } => match remove_path(doc, &path) {
+                Some(value) => Ok(Change::Insert { path, value }),
+                None => Err(not_available(&path)),
+            },
+
+            // A failing change reverts the changes of the batch which were already applied
+            Change::Batch(changes) => {
+                let mut reverts = Vec::with_capacity(changes.len());
+                for change in changes {
+                    match change.apply(doc) {
+                        Ok(revert) => reverts.push(revert),
+                        Err(e) => {
+                            for revert in reverts.into_iter().rev() {
+                                let _ = revert.apply(doc);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                reverts.reverse();
+                Ok(Change::Batch(reverts))
+            }
+        }
+    }
+}
+
+fn not_available(path: &[Segment]) -> Error {
+    Error::NotAvailable(format!("{:?}", path))
+}
+
+/// An entry on the undo or redo stack
+#[derive(Debug, Clone)]
+struct Entry {
+    id: usize,
+    change: Change,
+}
+
+/// A point in the history of an `EditHistory`, see `EditHistory::checkpoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(Option<usize>);
+
+/// A toml::Value document which records every modification, so it can be undone and redone
+///
+/// The `EditHistory` implements the set, insert, delete and restructure extension traits. Every
+/// successful modification made through them is recorded as the operation which reverts it,
+/// built from the old value the modification returns.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate toml;
+/// extern crate toml_query;
+///
+/// use toml_query::history::EditHistory;
+/// use toml_query::insert::TomlValueInsertExt;
+/// use toml_query::set::TomlValueSetExt;
+///
+/// let toml : toml::Value = toml::from_str("a = 1").unwrap();
+/// let mut history = EditHistory::new(toml.clone());
+///
+/// history.set("a", toml::Value::Integer(2)).unwrap();
+/// history.insert("b.c", toml::Value::Integer(3)).unwrap();
+///
+/// history.undo().unwrap();
+/// history.undo().unwrap();
+/// assert_eq!(history.document(), &toml);
+///
+/// history.redo().unwrap();
+/// assert_eq!(history.document(), &toml::from_str::<toml::Value>("a = 2").unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct EditHistory {
+    document: Value,
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    next_id: usize,
+}
+
+impl EditHistory {
+    /// Start recording the modifications on `document`
+    pub fn new(document: Value) -> EditHistory {
+        EditHistory {
+            document,
+            undo: vec![],
+            redo: vec![],
+            next_id: 0,
+        }
+    }
+
+    /// The current state of the document
+    pub fn document(&self) -> &Value {
+        &self.document
+    }
+
+    /// Stop recording and get the current state of the document
+    pub fn into_document(self) -> Value {
+        self.document
+    }
+
+    /// Check whether there is a modification which can be undone
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Check whether there is an undone modification which can be redone
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Undo the last modification
+    ///
+    /// Returns `Ok(false)` if there was nothing to undo. If undoing fails, the document and the
+    /// history are left unchanged.
+    pub fn undo(&mut self) -> Result<bool> {
+        match self.undo.last() {
+            None => Ok(false),
+            Some(Entry { id, change }) => {
+                let id = *id;
+                let change = change.clone().apply(&mut self.document)?;
+                self.undo.pop();
+                self.redo.push(Entry { id, change });
+                Ok(true)
+            }
+        }
+    }
+
+    /// Redo the last undone modification
+    ///
+    /// Returns `Ok(false)` if there was nothing to redo. Redoing is only possible until the
+    /// document is modified again. If redoing fails, the document and the history are left
+    /// unchanged.
+    pub fn redo(&mut self) -> Result<bool> {
+        match self.redo.last() {
+            None => Ok(false),
+            Some(Entry { id, change }) => {
+                let id = *id;
+                let change = change.clone().apply(&mut self.document)?;
+                self.redo.pop();
+                self.undo.push(Entry { id, change });
+                Ok(true)
+            }
+        }
+    }
+
+    /// Get a checkpoint of the current state of the document
+    ///
+    /// See documentation of `EditHistory::undo_to`
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.undo.last().map(|e| e.id))
+    }
+
+    /// Undo all modifications made since `checkpoint` was taken
+    ///
+    /// The undone modifications can be redone one by one.
+    ///
+    /// # Return value
+    ///
+    /// On failure, `Err(e)` is returned:
+    ///
+    /// * If the last modification before the checkpoint was undone in the meantime: error
+    ///
+    pub fn undo_to(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        if let Checkpoint(Some(id)) = checkpoint {
+            if !self.undo.iter().any(|e| e.id == id) {
+                return Err(Error::UnknownCheckpoint);
+            }
+        }
+
+        while self.checkpoint() != checkpoint {
+            self.undo()?;
+        }
+
+        Ok(())
+    }
+
+    /// Forget all recorded modifications
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn record(&mut self, change: Change) {
+        trace!("Recording {:?}", change);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.undo.push(Entry { id, change });
+        self.redo.clear();
+    }
+
+    /// Get the paths of the values a bulk operation with `pattern` modifies
+    fn matching_paths(&self, pattern: &str, sep: char) -> Result<Vec<Vec<Segment>>> {
+        use crate::resolver::multi_resolver::resolve_outermost;
+
+        let tokens = tokenize_with_seperator(pattern, sep)?;
+        Ok(resolve_outermost(&self.document, &tokens))
+    }
+
+    /// Get the change which reverts inserting a value at `path` into the current document,
+    /// from the old value the insert returns
+    fn insert_revert(&self, path: Vec<Segment>) -> impl FnOnce(Option<Value>) -> Change {
+        let parent = path[..path.len() - 1].to_vec();
+        let len = self.array_len(&parent);
+
+        // The first table which does not exist yet and is created by the insert
+        let created = (1..path.len())
+            .find(|i| get_path(&self.document, &path[..*i]).is_none())
+            .map(|i| path[..i].to_vec());
+
+        move |old| match (created, old, path.last()) {
+            (Some(path), _, _) => Change::Remove { path },
+            (None, Some(value), _) => Change::Replace { path, value },
+            (None, None, Some(Segment::Index(idx))) if Some(*idx) < len => Change::Remove { path },
+            (None, None, Some(Segment::Index(_))) => Change::Remove {
+                path: appended(&parent, len),
+            },
+            (None, None, _) => Change::Remove { path },
+        }
+    }
+
+    /// Get the length of the array at `path`
+    fn array_len(&self, path: &[Segment]) -> Option<usize> {
+        match get_path(&self.document, path) {
+            Some(Value::Array(ref a)) => Some(a.len()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for EditHistory {
+    fn from(document: Value) -> EditHistory {
+        EditHistory::new(document)
+    }
+}
+
+/// Get the path of the element which is appended to the array at `parent`
+fn appended(parent: &[Segment], len: Option<usize>) -> Vec<Segment> {
+    let mut path = parent.to_vec();
+    path.push(Segment::Index(len.unwrap_or(0)));
+    path
+}
+
+impl TomlValueSetExt for EditHistory {
+    fn set_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        let parent = &path[..path.len() - 1];
+        let len = self.array_len(parent);
+
+        let old = self.document.set_with_seperator(query, sep, value)?;
+
+        let change = match (old.clone(), path.last()) {
+            (Some(value), _) => Change::Replace { path, value },
+            (None, Some(Segment::Index(_))) => Change::Remove {
+                path: appended(parent, len),
+            },
+            (None, _) => Change::Remove { path },
+        };
+        self.record(change);
+
+        Ok(old)
+    }
+
+    fn set_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<(String, Value)>> {
+        let paths = self.matching_paths(pattern, sep)?;
+
+        let replaced = self.document.set_all_with_seperator(pattern, sep, value)?;
+
+        let changes = paths
+            .into_iter()
+            .zip(replaced.iter())
+            .map(|(path, (_, value))| Change::Replace {
+                path,
+                value: value.clone(),
+            })
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            self.record(Change::Batch(changes));
+        }
+
+        Ok(replaced)
+    }
+
+    fn set_with_options_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+
+        // The first table which does not exist yet and is created by the set
+        let created = (1..path.len())
+            .find(|i| get_path(&self.document, &path[..*i]).is_none())
+            .map(|i| path[..i].to_vec());
+
+        match created {
+            Some(path) if options.create_parents => {
+                let old = self
+                    .document
+                    .set_with_options_and_seperator(query, sep, value, options)?;
+                self.record(Change::Remove { path });
+                Ok(old)
+            }
+            _ => {
+                let value = options.prepare(&self.document, query, sep, value)?;
+                self.set_with_seperator(query, sep, value)
+            }
+        }
+    }
+}
+
+impl TomlValueInsertExt for EditHistory {
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        let revert = self.insert_revert(path);
+
+        let old = self.document.insert_with_seperator(query, sep, value)?;
+
+        self.record(revert(old.clone()));
+
+        Ok(old)
+    }
+}
+
+impl TomlValueDeleteExt for EditHistory {
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+
+        let old = self.document.delete_with_seperator(query, sep)?;
+
+        if let Some(ref value) = old {
+            let value = value.clone();
+            self.record(Change::Insert { path, value });
+        }
+
+        Ok(old)
+    }
+
+    fn delete_recursive_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        prune: bool,
+    ) -> Result<Option<Value>> {
+        let mut path = token_path(&tokenize_with_seperator(query, sep)?);
+
+        // The topmost table which becomes empty and is pruned
+        while prune && path.len() > 1 {
+            let parent = &path[..path.len() - 1];
+            match (get_path(&self.document, parent), parent.last()) {
+                (Some(Value::Table(ref tab)), Some(Segment::Key(_))) if tab.len() == 1 => {
+                    path.pop()
+                }
+                _ => break,
+            };
+        }
+        let removed = get_path(&self.document, &path).cloned();
+
+        let old = self
+            .document
+            .delete_recursive_with_seperator(query, sep, prune)?;
+
+        if let (Some(_), Some(value)) = (&old, removed) {
+            self.record(Change::Insert { path, value });
+        }
+
+        Ok(old)
+    }
+
+    fn delete_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+    ) -> Result<Vec<(String, Value)>> {
+        let paths = self.matching_paths(pattern, sep)?;
+
+        let deleted = self.document.delete_all_with_seperator(pattern, sep)?;
+
+        // Reinserting in document order restores the original indexes of array elements
+        let changes = paths
+            .into_iter()
+            .zip(deleted.iter())
+            .map(|(path, (_, value))| Change::Insert {
+                path,
+                value: value.clone(),
+            })
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            self.record(Change::Batch(changes));
+        }
+
+        Ok(deleted)
+    }
+}
+
+impl TomlValueRestructureExt for EditHistory {
+    fn move_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        let from_path = token_path(&tokenize_with_seperator(from, sep)?);
+        let to_path = token_path(&tokenize_with_seperator(to, sep)?);
+
+        if to_path.starts_with(&from_path) {
+            return Err(Error::CannotMoveIntoItself(from.to_owned(), to.to_owned()));
+        }
+
+        let value = remove_path(&mut self.document, &from_path)
+            .ok_or_else(|| Error::NotAvailable(from.to_owned()))?;
+
+        // The target is resolved after the value was removed, like for toml::Value
+        let revert = self.insert_revert(to_path);
+        match self.document.insert_with_seperator(to, sep, value.clone()) {
+            Ok(old) => {
+                let change = Change::Batch(vec![
+                    revert(old.clone()),
+                    Change::Insert {
+                        path: from_path,
+                        value,
+                    },
+                ]);
+                self.record(change);
+                Ok(old)
+            }
+            Err(e) => {
+                let _ = insert_path(&mut self.document, &from_path, value); // restore the source
+                Err(e)
+            }
+        }
+    }
+
+    fn copy_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        let value = self
+            .document
+            .read_with_seperator(from, sep)?
+            .cloned()
+            .ok_or_else(|| Error::NotAvailable(from.to_owned()))?;
+
+        self.insert_with_seperator(to, sep, value)
+    }
+
+    fn rename_key_with_seperator(
+        &mut self,
+        query: &str,
+        new_name: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        let parent = &path[..path.len() - 1];
+
+        // Replacing the whole table also restores the position of the key
+        let table = get_path(&self.document, parent).cloned();
+
+        let old = self
+            .document
+            .rename_key_with_seperator(query, new_name, sep)?;
+
+        if let Some(value) = table {
+            if get_path(&self.document, parent) != Some(&value) {
+                let path = parent.to_vec();
+                self.record(Change::Replace { path, value });
+            }
+        }
+
+        Ok(old)
+    }
+}
+
+impl TomlValueUpdateExt for EditHistory {
+    fn update_with_seperator<F>(&mut self, query: &str, sep: char, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        // Modify a copy, so the value is replaced through set and the change is recorded
+        let mut value = self
+            .document
+            .read_with_seperator(query, sep)?
+            .cloned()
+            .ok_or_else(|| Error::NotAvailable(query.to_owned()))?;
+
+        f(&mut value)?;
+        self.set_with_seperator(query, sep, value).map(|_| ())
+    }
+
+    fn update_or_insert_with_seperator<F>(
+        &mut self,
+        query: &str,
+        sep: char,
+        default: Value,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        if self.document.read_with_seperator(query, sep)?.is_some() {
+            self.update_with_seperator(query, sep, f)
+        } else {
+            self.insert_with_seperator(query, sep, default).map(|_| ())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    /// Undo everything and check the original document is restored, then redo everything and
+    /// check the modified document is restored
+    fn assert_undo_redo(history: &mut EditHistory, original: &Value) {
+        let modified = history.document().clone();
+
+        while history.undo().unwrap() {}
+        assert_eq!(history.document(), original);
+
+        while history.redo().unwrap() {}
+        assert_eq!(history.document(), &modified);
+    }
+
+    #[test]
+    fn test_undo_set() {
+        let original: Value = toml_from_str("[table]\na = 1\narray = [1, 2]").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history.set("table.a", Value::Integer(2)).unwrap();
+        history.set("table.b", Value::Integer(3)).unwrap();
+        history.set("table.array.[0]", Value::Integer(4)).unwrap();
+        history.set("table.array.[10]", Value::Integer(5)).unwrap();
+
+        let expected: Value = toml_from_str("[table]\na = 2\nb = 3\narray = [4, 2, 5]").unwrap();
+        assert_eq!(history.document(), &expected);
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_insert() {
+        let original: Value = toml_from_str("[table]\narray = [1, 2]").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history
+            .insert("table.array.[0]", Value::Integer(3))
+            .unwrap();
+        history
+            .insert("table.array.[10]", Value::Integer(4))
+            .unwrap();
+        history.insert("a.b.c", Value::Integer(5)).unwrap();
+        history.insert("table.x", Value::Integer(6)).unwrap();
+        history.insert("table.x", Value::Integer(7)).unwrap();
+
+        let expected: Value =
+            toml_from_str("[table]\narray = [3, 1, 2, 4]\nx = 7\n[a.b]\nc = 5").unwrap();
+        assert_eq!(history.document(), &expected);
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_delete() {
+        let original: Value = toml_from_str("a = 1\narray = [1, 2, 3]\n[table]").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history.delete("a").unwrap();
+        history.delete("array.[1]").unwrap();
+        history.delete("table").unwrap();
+        history.delete("nothing").unwrap();
+
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("array = [1, 3]").unwrap()
+        );
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_delete_recursive() {
+        let original: Value =
+            toml_from_str("[a]\nx = [1]\n[a.b.c]\nd = [1]\n[e]\nf = 1\n[[g]]\nh = 1").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history.delete_recursive("a.x").unwrap();
+        history.delete_recursive_and_prune("a.b.c.d").unwrap();
+        history.delete_recursive_and_prune("e.f").unwrap();
+        history.delete_recursive_and_prune("g.[0].h").unwrap();
+
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("g = [{}]").unwrap()
+        );
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_bulk_operations() {
+        let original: Value =
+            toml_from_str("a = [{ p = 1 }, { q = 2 }, { p = 3 }]\n[b]\np = 4").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history.set_all("**.p", Value::Integer(0)).unwrap();
+        history.delete_all("a.*").unwrap();
+        history.delete_all("nothing").unwrap();
+
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("a = []\n[b]\np = 0").unwrap()
+        );
+
+        history.undo().unwrap();
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("a = [{ p = 0 }, { q = 2 }, { p = 0 }]\n[b]\np = 0").unwrap()
+        );
+
+        history.redo().unwrap();
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_update() {
+        use crate::update::TomlValueUpdateTypeExt;
+
+        let original: Value = toml_from_str("[counter]\nvalue = 1").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history.update_int("counter.value", |i| i + 1).unwrap();
+        history
+            .update_or_insert("counter.step", Value::Integer(1), |_| Ok(()))
+            .unwrap();
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("[counter]\nvalue = 2\nstep = 1").unwrap()
+        );
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_set_or_create() {
+        let original: Value = toml_from_str("[a]\nx = 1").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history.set_or_create("a.b.c", Value::Integer(1)).unwrap();
+        history.set_or_create("a.b.c", Value::Integer(2)).unwrap();
+        history.set_or_create("a.x", Value::Integer(3)).unwrap();
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("[a]\nx = 3\n[a.b]\nc = 2").unwrap()
+        );
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_undo_restructure() {
+        let original: Value =
+            toml_from_str("a = [1, 2, 3]\n[server]\naddr = \"x\"\nport = 80\n[other]\nk = 1")
+                .unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        history
+            .move_value("server.addr", "network.listen.addr")
+            .unwrap();
+        history.move_value("a.[0]", "a.[2]").unwrap();
+        history.copy_value("other.k", "server.k").unwrap();
+        history.rename_key("server.port", "listen_port").unwrap();
+        history.rename_key("other.k", "k").unwrap();
+
+        let expected: Value = toml_from_str(
+            r#"
+        a = [2, 3, 1]
+        [server]
+        listen_port = 80
+        k = 1
+        [other]
+        k = 1
+        [network.listen]
+        addr = "x"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(history.document(), &expected);
+
+        assert_undo_redo(&mut history, &original);
+    }
+
+    #[test]
+    fn test_failed_move_is_not_recorded() {
+        let original: Value = toml_from_str("a = [1]\n[b]\nc = 1").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        assert!(history.move_value("b.c", "a.x").is_err());
+        assert!(history.move_value("b", "b.c").is_err());
+        assert_eq!(history.document(), &original);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_rejected_set_same_type_is_not_recorded() {
+        let mut history = EditHistory::new(toml_from_str::<Value>("a = 1").unwrap());
+
+        history.set_same_type("a", Value::Integer(2)).unwrap();
+        let res = history.set_same_type("a", Value::Float(2.0));
+
+        assert!(is_match!(
+            res,
+            Err(Error::CannotChangeType(_, "Integer", "Float"))
+        ));
+        assert!(history.undo().unwrap());
+        assert!(!history.can_undo());
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("a = 1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_failed_update_is_not_recorded() {
+        let mut history = EditHistory::new(toml_from_str::<Value>("a = 1").unwrap());
+
+        let res = history.update("a", |a| {
+            *a = Value::Integer(2);
+            Err(Error::EmptyQueryError)
+        });
+
+        assert!(res.is_err());
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("a = 1").unwrap()
+        );
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_failed_insert_is_not_recorded() {
+        let original: Value = toml_from_str("").unwrap();
+        let mut history = EditHistory::new(original.clone());
+
+        assert!(history.insert("a.b.[0]", Value::Integer(1)).is_err());
+        assert_eq!(history.document(), &original);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_failed_undo_of_batch_changes_nothing() {
+        let mut history = EditHistory::new(toml_from_str::<Value>("a = 1\nb = 2").unwrap());
+        history.set_all("*", Value::Integer(0)).unwrap();
+
+        // Reverting the batch sets `a` first and fails on `b`
+        history.document.as_table_mut().unwrap().remove("b");
+
+        assert!(history.undo().is_err());
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("a = 0").unwrap()
+        );
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_modification_clears_redo() {
+        let mut history = EditHistory::new(toml_from_str::<Value>("a = 1").unwrap());
+
+        history.set("a", Value::Integer(2)).unwrap();
+        history.undo().unwrap();
+        assert!(history.can_redo());
+
+        history.set("a", Value::Integer(3)).unwrap();
+        assert!(!history.can_redo());
+        assert!(!history.redo().unwrap());
+    }
+
+    #[test]
+    fn test_undo_to_checkpoint() {
+        let mut history = EditHistory::new(toml_from_str::<Value>("a = 1").unwrap());
+
+        history.set("a", Value::Integer(2)).unwrap();
+        let checkpoint = history.checkpoint();
+        history.set("a", Value::Integer(3)).unwrap();
+        history.insert("b", Value::Integer(4)).unwrap();
+
+        history.undo_to(checkpoint).unwrap();
+        assert_eq!(
+            history.document(),
+            &toml_from_str::<Value>("a = 2").unwrap()
+        );
+
+        history.undo().unwrap();
+        history.set("a", Value::Integer(5)).unwrap();
+        assert!(is_match!(
+            history.undo_to(checkpoint),
+            Err(Error::UnknownCheckpoint)
+        ));
+    }
+}
@@ -1,12 +1,18 @@
-/// The Toml Insert extensions
+//! The Toml Insert extensions
 
 #[cfg(feature = "typed")]
 use serde::Serialize;
 use toml::Value;
 
-use crate::error::{Error, Result};
+#[cfg(feature = "typed")]
+use crate::delete::TomlValueDeleteExt;
+#[cfg(feature = "typed")]
+use crate::error::Error;
+use crate::error::Result;
+#[cfg(feature = "typed")]
+use crate::read::Partial;
 use crate::tokenizer::tokenize_with_seperator;
-use crate::tokenizer::Token;
+use crate::util::{get_path, remove_path, token_path};
 
 pub trait TomlValueInsertExt {
     /// Extension function for inserting a value in the current toml::Value document
@@ -29,6 +35,8 @@ pub trait TomlValueInsertExt {
     /// If a Value is inserted into an Array, the array indexes are shifted. Semantically this is
     /// the same as doing a `array.insert(4, _)` (see the standard library).
     ///
+    /// If the insert operation fails, the intermediate tables it created are removed again.
+    ///
     /// ## Known Bugs
     ///
     /// The current implementation does _not_ create intermediate Arrays as described above.
@@ -97,6 +105,22 @@ pub trait TomlValueInsertExt {
         let value = Value::try_from(value).map_err(Error::TomlSerialize)?;
         self.insert(query, value)
     }
+
+    /// Extension function for writing a `Partial` to its location in the current toml::Value
+    /// document
+    ///
+    /// The `Partial` is written with `Partial::insert_in`, which by default inserts the
+    /// serialized value at `P::LOCATION` like with `TomlValueInsertExt::insert`. Types with field
+    /// locations delete the values of missing fields, which is why the document must support
+    /// deleting as well.
+    #[cfg(feature = "typed")]
+    fn insert_partial<'a, P>(&mut self, value: &P) -> Result<Option<Value>>
+    where
+        P: Partial<'a, Output = P>,
+        Self: TomlValueDeleteExt,
+    {
+        P::insert_in(value, self)
+    }
 }
 
 impl TomlValueInsertExt for Value {
@@ -109,36 +133,32 @@ impl TomlValueInsertExt for Value {
         use crate::resolver::mut_creating_resolver::resolve;
 
         let mut tokens = tokenize_with_seperator(query, sep)?;
-        let (val, last) = match tokens.pop_last() {
-            None => (self, Box::new(tokens)),
-            Some(last) => (resolve(self, &tokens)?, last),
+
+        // The first table which does not exist yet and is created
+        let path = token_path(&tokens);
+        let created = (1..path.len())
+            .find(|i| get_path(self, &path[..*i]).is_none())
+            .map(|i| path[..i].to_vec());
+
+        let result = match tokens.pop_last() {
+            None => crate::queryable::insert_last(self, tokens, value),
+            Some(last) => resolve(self, &tokens)
+                .and_then(|val| crate::queryable::insert_last(val, *last, value)),
         };
 
-        match *last {
-            Token::Identifier { ident, .. } => match val {
-                Value::Table(ref mut t) => Ok(t.insert(ident, value)),
-                _ => Err(Error::NoIdentifierInArray(ident.clone())),
-            },
-
-            Token::Index { idx, .. } => match val {
-                Value::Array(ref mut a) => {
-                    if a.len() > idx {
-                        a.insert(idx, value);
-                        Ok(None)
-                    } else {
-                        a.push(value);
-                        Ok(None)
-                    }
-                }
-                _ => Err(Error::NoIndexInTable(idx)),
-            },
+        if let (Err(_), Some(path)) = (&result, created) {
+            // Do not leave the created tables behind
+            remove_path(self, &path);
         }
+
+        result
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::Error;
     use toml::from_str as toml_from_str;
     use toml::Value;
 
@@ -442,4 +462,37 @@ mod test {
             _ => panic!("What just happenend?"),
         }
     }
+
+    #[test]
+    fn test_insert_failure_removes_created_tables() {
+        let mut toml: Value = toml_from_str("[a]\nx = 1").unwrap();
+
+        let res = toml.insert("a.b.c.[0]", Value::Integer(1));
+
+        assert!(res.is_err());
+        assert_eq!(toml, toml_from_str::<Value>("[a]\nx = 1").unwrap());
+    }
+
+    #[cfg(feature = "typed")]
+    #[test]
+    fn test_insert_partial() {
+        use crate::read::{Partial, TomlValueReadExt};
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Cache {
+            size: u64,
+        }
+
+        impl<'a> Partial<'a> for Cache {
+            const LOCATION: &'static str = "services.[0].cache";
+            type Output = Self;
+        }
+
+        let mut toml: Value = toml_from_str("services = [{ name = \"web\" }]").unwrap();
+        let cache = Cache { size: 10 };
+
+        assert!(toml.insert_partial(&cache).unwrap().is_none());
+        assert_eq!(toml.read_partial::<Cache>().unwrap(), Some(cache));
+        assert!(toml.read("services.[0].name").unwrap().is_some());
+    }
 }
@@ -1,14 +1,17 @@
 #![recursion_limit = "1024"]
 #![warn(rust_2018_idioms)]
+// failure_derive expands to impls inside an anonymous const
+#![allow(non_local_definitions)]
 // We need this for error_chain, unfortunately.
 
-/// # toml-query
-///
-/// A crate to help executing queries on toml data structures inside Rust code.
-///
+//! # toml-query
+//!
+//! A crate to help executing queries on toml data structures inside Rust code.
+//!
 
 // external crates
 
+#[cfg(test)]
 #[macro_use]
 extern crate is_match;
 #[macro_use]
@@ -28,6 +31,10 @@ extern crate serde_derive;
 #[macro_use]
 extern crate quickcheck;
 
+// The code generated by toml-query_derive refers to the crate by name
+#[cfg(all(test, feature = "typed"))]
+extern crate self as toml_query;
+
 // public modules
 
 #[cfg(not(feature = "log"))]
@@ -37,12 +44,33 @@ pub mod log;
 #[doc(hidden)]
 pub use toml_query_derive::*;
 
+pub mod array;
+#[cfg(feature = "edit")]
+pub mod comment;
 pub mod delete;
+#[cfg(feature = "typed")]
+pub mod deserializer;
+pub mod dry_run;
+#[cfg(feature = "edit")]
+pub mod edit;
+pub mod entry;
 pub mod error;
+pub mod guard;
+pub mod history;
 pub mod insert;
+pub mod merge;
+pub mod patch;
+pub mod queryable;
 pub mod read;
+pub mod restructure;
+mod root;
+#[cfg(feature = "typed")]
+pub mod serializer;
 pub mod set;
+pub mod transaction;
+pub mod update;
 mod util;
+pub mod validate;
 pub mod value;
 
 // private modules
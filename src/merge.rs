@@ -0,0 +1,391 @@
+//! Three-way merging of toml::Value documents
+
+use toml::map::Map;
+use toml::Value;
+
+use crate::error::{Error, Result};
+use crate::util::{name_of_val, path_to_query, Segment};
+
+/// A change which was made differently in both documents and could not be merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// The query of the conflicting value
+    pub query: String,
+
+    /// The value in the base document, `None` if it is not present
+    pub base: Option<Value>,
+
+    /// The value in our document, `None` if it was deleted or is not present
+    pub ours: Option<Value>,
+
+    /// The value in their document, `None` if it was deleted or is not present
+    pub theirs: Option<Value>,
+}
+
+impl Conflict {
+    /// Get the conflict as a table with the keys `base`, `ours` and `theirs`
+    ///
+    /// Keys for values which are not present are left out.
+    pub fn to_table(&self) -> Value {
+        let mut tab = Map::new();
+        let sides = vec![
+            ("base", &self.base),
+            ("ours", &self.ours),
+            ("theirs", &self.theirs),
+        ];
+        for (name, value) in sides {
+            if let Some(value) = value {
+                tab.insert(String::from(name), value.clone());
+            }
+        }
+        Value::Table(tab)
+    }
+}
+
+/// The result of a three-way merge
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    merged: Value,
+    conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    /// The merged document
+    pub fn merged(&self) -> &Value {
+        &self.merged
+    }
+
+    /// The conflicts of this merge, ordered by their position in the documents
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Check whether the merge has conflicts
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Get all conflicts as a table, with the query of each conflict as key
+    ///
+    /// See documentation of `Conflict::to_table`
+    pub fn conflict_table(&self) -> Value {
+        let tab = self
+            .conflicts
+            .iter()
+            .map(|c| (c.query.clone(), c.to_table()))
+            .collect();
+
+        Value::Table(tab)
+    }
+
+    /// Get the merged document
+    pub fn into_merged(self) -> Value {
+        self.merged
+    }
+
+    /// Get the merged document, with the conflicts added as a table at the top-level key `key`
+    ///
+    /// If there are no conflicts, no table is added. An existing value at `key` is replaced.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(merged)` with the merged document
+    /// * `Err(e)` if there are conflicts and the root of the merged document is not a table
+    ///
+    /// See documentation of `Merge::conflict_table`
+    pub fn into_merged_with_conflict_table(self, key: &str) -> Result<Value> {
+        if self.conflicts.is_empty() {
+            return Ok(self.merged);
+        }
+
+        let table = self.conflict_table();
+        match self.merged {
+            Value::Table(mut tab) => {
+                tab.insert(String::from(key), table);
+                Ok(Value::Table(tab))
+            }
+            other => Err(Error::CannotAddConflictTable(name_of_val(&other))),
+        }
+    }
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`
+///
+/// See documentation of `merge_with_seperator`
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Result<Merge> {
+    merge_with_seperator(base, ours, theirs, '.')
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`, building the queries
+/// of conflicts with a custom seperator
+///
+/// # Semantics
+///
+/// A value which was changed in only one of the documents, or which was changed the same way
+/// in both documents, is taken over into the merged document. Tables are merged key by key,
+/// arrays are merged element by element if they have the same length in all three documents.
+///
+/// Every other value which was changed in both documents is a conflict. The merged document
+/// contains our side of a conflict.
+///
+/// # Return value
+///
+/// * If the merge worked, `Ok(merge)` is returned, even if there are conflicts
+/// * On failure, `Err(e)` is returned:
+///     * If there is a conflict at the root of the documents: error
+///     * If the key of a conflict cannot be expressed in a query: error
+///
+pub fn merge_with_seperator(
+    base: &Value,
+    ours: &Value,
+    theirs: &Value,
+    sep: char,
+) -> Result<Merge> {
+    let mut conflicts = vec![];
+    let mut path = vec![];
+    let merged = merge_value(
+        &mut path,
+        Some(base),
+        Some(ours),
+        Some(theirs),
+        sep,
+        &mut conflicts,
+    )?;
+
+    match merged {
+        Some(merged) => Ok(Merge { merged, conflicts }),
+        None => unreachable!("Root cannot be deleted"),
+    }
+}
+
+fn merge_value(
+    path: &mut Vec<Segment>,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    sep: char,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<Option<Value>> {
+    if ours == theirs || base == theirs {
+        return Ok(ours.cloned());
+    }
+    if base == ours {
+        return Ok(theirs.cloned());
+    }
+
+    match (base, ours, theirs) {
+        (None, Some(Value::Table(ref o)), Some(Value::Table(ref t)))
+        | (Some(Value::Table(_)), Some(Value::Table(ref o)), Some(Value::Table(ref t))) => {
+            let empty = Map::new();
+            let b = match base {
+                Some(Value::Table(ref b)) => b,
+                _ => &empty,
+            };
+
+            let mut keys = b.keys().chain(o.keys()).chain(t.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+
+            let mut merged = Map::new();
+            for key in keys {
+                path.push(Segment::Key(key.clone()));
+                let value = merge_value(path, b.get(key), o.get(key), t.get(key), sep, conflicts)?;
+                path.pop();
+
+                if let Some(value) = value {
+                    merged.insert(key.clone(), value);
+                }
+            }
+
+            Ok(Some(Value::Table(merged)))
+        }
+
+        (Some(Value::Array(ref b)), Some(Value::Array(ref o)), Some(Value::Array(ref t)))
+            if b.len() == o.len() && b.len() == t.len() =>
+        {
+            let mut merged = Vec::with_capacity(b.len());
+            for (idx, ((b, o), t)) in b.iter().zip(o.iter()).zip(t.iter()).enumerate() {
+                path.push(Segment::Index(idx));
+                let value = merge_value(path, Some(b), Some(o), Some(t), sep, conflicts)?;
+                path.pop();
+
+                // Elements are only removed if both sides removed them, which cannot happen here
+                merged.extend(value);
+            }
+
+            Ok(Some(Value::Array(merged)))
+        }
+
+        _ => {
+            if path.is_empty() {
+                let name = |v: Option<&Value>| v.map(crate::util::name_of_val).unwrap_or("None");
+                return Err(Error::CannotMergeRoot(name(ours), name(theirs)));
+            }
+
+            conflicts.push(Conflict {
+                query: path_to_query(path, sep)?,
+                base: base.cloned(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+
+            Ok(ours.cloned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn do_merge(base: &str, ours: &str, theirs: &str) -> Merge {
+        let base: Value = toml_from_str(base).unwrap();
+        let ours: Value = toml_from_str(ours).unwrap();
+        let theirs: Value = toml_from_str(theirs).unwrap();
+        merge(&base, &ours, &theirs).unwrap()
+    }
+
+    #[test]
+    fn test_merge_non_overlapping_changes() {
+        let merge = do_merge(
+            "[server]\nport = 80\nhost = \"localhost\"\n[log]\nlevel = \"info\"",
+            "[server]\nport = 8080\nhost = \"localhost\"\n[log]\nlevel = \"info\"",
+            "[server]\nport = 80\nhost = \"localhost\"\n[log]\nlevel = \"debug\"\nfile = \"x\"",
+        );
+
+        assert!(!merge.has_conflicts());
+        let expected: Value = toml_from_str(
+            "[server]\nport = 8080\nhost = \"localhost\"\n[log]\nlevel = \"debug\"\nfile = \"x\"",
+        )
+        .unwrap();
+        assert_eq!(merge.merged(), &expected);
+    }
+
+    #[test]
+    fn test_merge_same_change_on_both_sides() {
+        let merge = do_merge("a = 1", "a = 2", "a = 2");
+
+        assert!(!merge.has_conflicts());
+        assert_eq!(merge.merged(), &toml_from_str::<Value>("a = 2").unwrap());
+    }
+
+    #[test]
+    fn test_merge_deletions() {
+        let merge = do_merge("a = 1\nb = 2\nc = 3", "b = 2\nc = 3", "a = 1\nc = 3");
+
+        assert!(!merge.has_conflicts());
+        assert_eq!(merge.merged(), &toml_from_str::<Value>("c = 3").unwrap());
+    }
+
+    #[test]
+    fn test_merge_added_tables_on_both_sides() {
+        let merge = do_merge("", "[new]\na = 1", "[new]\nb = 2");
+
+        assert!(!merge.has_conflicts());
+        assert_eq!(
+            merge.merged(),
+            &toml_from_str::<Value>("[new]\na = 1\nb = 2").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_arrays_elementwise() {
+        let merge = do_merge("a = [1, 2, 3]", "a = [5, 2, 3]", "a = [1, 2, 6]");
+
+        assert!(!merge.has_conflicts());
+        assert_eq!(
+            merge.merged(),
+            &toml_from_str::<Value>("a = [5, 2, 6]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict() {
+        let merge = do_merge("[a]\nb = 1", "[a]\nb = 2", "[a]\nb = 3");
+
+        assert_eq!(
+            merge.conflicts(),
+            &[Conflict {
+                query: String::from("a.b"),
+                base: Some(Value::Integer(1)),
+                ours: Some(Value::Integer(2)),
+                theirs: Some(Value::Integer(3)),
+            }]
+        );
+        assert_eq!(
+            merge.merged(),
+            &toml_from_str::<Value>("[a]\nb = 2").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict_change_and_delete() {
+        let merge = do_merge("a = [1]\nb = 0", "b = 0", "a = [1, 2]\nb = 0");
+
+        assert_eq!(merge.conflicts().len(), 1);
+        assert_eq!(merge.conflicts()[0].query, "a");
+        assert!(merge.conflicts()[0].ours.is_none());
+        assert_eq!(merge.merged(), &toml_from_str::<Value>("b = 0").unwrap());
+    }
+
+    #[test]
+    fn test_merge_conflict_in_array() {
+        let merge = do_merge("a = [1, 2]", "a = [1, 3]", "a = [1, 4]");
+
+        assert_eq!(merge.conflicts().len(), 1);
+        assert_eq!(merge.conflicts()[0].query, "a.[1]");
+    }
+
+    #[test]
+    fn test_merge_conflict_table() {
+        let merge = do_merge("[a]\nb = 1\nc = 1", "[a]\nb = 2", "[a]\nb = 3\nc = 1");
+
+        let merged = merge.into_merged_with_conflict_table("conflicts").unwrap();
+        let expected: Value = toml_from_str(
+            r#"
+            [a]
+            b = 2
+
+            [conflicts."a.b"]
+            base = 1
+            ours = 2
+            theirs = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_merge_without_conflicts_has_no_conflict_table() {
+        let merge = do_merge("a = 1", "a = 2", "a = 1");
+        let merged = merge.into_merged_with_conflict_table("conflicts").unwrap();
+        assert_eq!(merged, toml_from_str::<Value>("a = 2").unwrap());
+    }
+
+    #[test]
+    fn test_merge_conflict_table_with_array_root() {
+        let base = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+        let ours = Value::Array(vec![Value::Integer(1), Value::Integer(3)]);
+        let theirs = Value::Array(vec![Value::Integer(1), Value::Integer(4)]);
+
+        let merge = merge(&base, &ours, &theirs).unwrap();
+        assert!(merge.has_conflicts());
+
+        let err = merge
+            .into_merged_with_conflict_table("conflicts")
+            .unwrap_err();
+        assert!(is_match!(err, Error::CannotAddConflictTable("Array")));
+    }
+
+    #[test]
+    fn test_merge_conflict_at_root() {
+        let base = Value::Integer(1);
+        let ours = Value::Integer(2);
+        let theirs = Value::Table(Map::new());
+
+        let err = merge(&base, &ours, &theirs).unwrap_err();
+        assert!(is_match!(err, Error::CannotMergeRoot("Integer", "Table")));
+    }
+}
@@ -0,0 +1,411 @@
+//! Computing and applying patches between two toml::Value documents
+
+use toml::Value;
+
+use crate::delete::TomlValueDeleteExt;
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::set::TomlValueSetExt;
+use crate::util::{index_query, key_query};
+
+/// A single operation of a `Patch`
+///
+/// Each operation maps directly onto one call of the extension traits of this crate, with the
+/// query built using the seperator of the `Patch` it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Replace the value at `query`, see `TomlValueSetExt::set_with_seperator`
+    Set { query: String, value: Value },
+
+    /// Insert a value at `query`, see `TomlValueInsertExt::insert_with_seperator`
+    Insert { query: String, value: Value },
+
+    /// Delete the value at `query`, see `TomlValueDeleteExt::delete_with_seperator`
+    Delete { query: String },
+}
+
+impl Operation {
+    /// The query this operation is executed at
+    pub fn query(&self) -> &str {
+        match self {
+            Operation::Set { ref query, .. } => query,
+            Operation::Insert { ref query, .. } => query,
+            Operation::Delete { ref query } => query,
+        }
+    }
+}
+
+/// An ordered list of operations which turns one document into another
+///
+/// # Semantics
+///
+/// The operations have to be applied in order. Array elements are only ever appended or removed
+/// from the end of an array, so no operation invalidates the index of a later one.
+///
+/// Because `TomlValueDeleteExt` refuses to delete non-empty tables and arrays, deleting such a
+/// structure is expressed as a `Set` to an empty structure of the same type, followed by a
+/// `Delete`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    seperator: char,
+    operations: Vec<Operation>,
+}
+
+impl Patch {
+    /// Compute the patch which turns `from` into `to`
+    ///
+    /// See documentation of `Patch::diff_with_seperator`
+    pub fn diff(from: &Value, to: &Value) -> Result<Patch> {
+        Patch::diff_with_seperator(from, to, '.')
+    }
+
+    /// Compute the patch which turns `from` into `to`, building queries with a custom seperator
+    ///
+    /// # Return value
+    ///
+    /// * If both documents are equal, an empty patch is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the documents differ at the root, but are not both tables or both arrays: error
+    ///     * If a key which needs to be touched cannot be expressed in a query (because it is
+    ///       empty, contains the seperator or looks like an array index): error
+    ///
+    pub fn diff_with_seperator(from: &Value, to: &Value, sep: char) -> Result<Patch> {
+        let mut operations = vec![];
+
+        match (from, to) {
+            (Value::Table(_), Value::Table(_)) | (Value::Array(_), Value::Array(_)) => {
+                diff_structure(None, from, to, sep, &mut operations)?
+            }
+            _ if from == to => {}
+            _ => {
+                let from = crate::util::name_of_val(from);
+                let to = crate::util::name_of_val(to);
+                return Err(Error::CannotPatchRoot(from, to));
+            }
+        }
+
+        Ok(Patch {
+            seperator: sep,
+            operations,
+        })
+    }
+
+    /// The seperator the queries of this patch are built with
+    pub fn seperator(&self) -> char {
+        self.seperator
+    }
+
+    /// The operations of this patch, in the order they have to be applied
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Check whether the patch does not contain any operation
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+pub trait TomlValuePatchExt {
+    /// Extension function for applying a `Patch` to the current toml::Value document
+    ///
+    /// # Semantics
+    ///
+    /// The operations are applied in order. If an operation fails, the function returns the
+    /// error immediately, leaving the document with all previous operations applied.
+    ///
+    fn apply_patch(&mut self, patch: &Patch) -> Result<()>;
+}
+
+impl TomlValuePatchExt for Value {
+    fn apply_patch(&mut self, patch: &Patch) -> Result<()> {
+        let sep = patch.seperator;
+
+        for operation in patch.operations.iter() {
+            trace!("Applying {:?}", operation);
+            match operation {
+                Operation::Set { query, value } => {
+                    self.set_with_seperator(query, sep, value.clone())?;
+                }
+                Operation::Insert { query, value } => {
+                    self.insert_with_seperator(query, sep, value.clone())?;
+                }
+                Operation::Delete { query } => {
+                    self.delete_with_seperator(query, sep)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get an empty structure of the same type as `val`, if `val` is a non-empty structure
+fn emptied(val: &Value) -> Option<Value> {
+    match val {
+        Value::Table(ref t) if !t.is_empty() => Some(Value::Table(Default::default())),
+        Value::Array(ref a) if !a.is_empty() => Some(Value::Array(vec![])),
+        _ => None,
+    }
+}
+
+fn push_delete(query: String, old: &Value, operations: &mut Vec<Operation>) {
+    if let Some(value) = emptied(old) {
+        operations.push(Operation::Set {
+            query: query.clone(),
+            value,
+        });
+    }
+    operations.push(Operation::Delete { query });
+}
+
+/// Diff a value which exists in both documents at `query`
+fn diff_value(
+    query: String,
+    from: &Value,
+    to: &Value,
+    sep: char,
+    operations: &mut Vec<Operation>,
+) -> Result<()> {
+    match (from, to) {
+        (Value::Table(_), Value::Table(_)) | (Value::Array(_), Value::Array(_)) => {
+            diff_structure(Some(&query), from, to, sep, operations)
+        }
+        _ if from == to => Ok(()),
+        _ => {
+            operations.push(Operation::Set {
+                query,
+                value: to.clone(),
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Diff two tables or two arrays at `prefix`, which is `None` for the document root
+fn diff_structure(
+    prefix: Option<&str>,
+    from: &Value,
+    to: &Value,
+    sep: char,
+    operations: &mut Vec<Operation>,
+) -> Result<()> {
+    match (from, to) {
+        (Value::Table(ref from), Value::Table(ref to)) => {
+            for (key, old) in from.iter() {
+                match to.get(key) {
+                    None => push_delete(key_query(prefix, key, sep)?, old, operations),
+                    Some(new) => {
+                        if old != new {
+                            diff_value(key_query(prefix, key, sep)?, old, new, sep, operations)?
+                        }
+                    }
+                }
+            }
+
+            for (key, new) in to.iter().filter(|(key, _)| !from.contains_key(*key)) {
+                operations.push(Operation::Insert {
+                    query: key_query(prefix, key, sep)?,
+                    value: new.clone(),
+                });
+            }
+        }
+
+        (Value::Array(ref from), Value::Array(ref to)) => {
+            for (idx, (old, new)) in from.iter().zip(to.iter()).enumerate() {
+                diff_value(index_query(prefix, idx, sep), old, new, sep, operations)?;
+            }
+
+            // Remove from the back, so the indexes of the remaining elements stay valid
+            for idx in (to.len()..from.len()).rev() {
+                push_delete(index_query(prefix, idx, sep), &from[idx], operations);
+            }
+
+            for (idx, new) in to.iter().enumerate().skip(from.len()) {
+                operations.push(Operation::Insert {
+                    query: index_query(prefix, idx, sep),
+                    value: new.clone(),
+                });
+            }
+        }
+
+        _ => unreachable!("diff_structure() is only called with two tables or two arrays"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{Arbitrary, Gen};
+    use toml::from_str as toml_from_str;
+
+    fn assert_roundtrip(from: &str, to: &str) -> Patch {
+        let from: Value = toml_from_str(from).unwrap();
+        let to: Value = toml_from_str(to).unwrap();
+
+        let patch = Patch::diff(&from, &to).unwrap();
+        let mut patched = from.clone();
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(patched, to, "Patch: {:?}", patch);
+        patch
+    }
+
+    #[test]
+    fn test_diff_equal_documents_is_empty() {
+        let patch = assert_roundtrip("a = 1\n[b]\nc = [1, 2]", "a = 1\n[b]\nc = [1, 2]");
+        assert!(patch.is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_value() {
+        let patch = assert_roundtrip("[table]\na = 1\nb = 2", "[table]\na = 1\nb = 3");
+
+        assert_eq!(
+            patch.operations(),
+            &[Operation::Set {
+                query: String::from("table.b"),
+                value: Value::Integer(3),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_changed_top_level_value() {
+        let patch = assert_roundtrip("a = 1", "a = \"one\"");
+
+        assert_eq!(
+            patch.operations(),
+            &[Operation::Set {
+                query: String::from("a"),
+                value: Value::String(String::from("one")),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_added_table() {
+        let patch = assert_roundtrip("a = 1", "a = 1\n[b.c]\nd = 2");
+
+        assert_eq!(patch.operations().len(), 1);
+        assert!(is_match!(patch.operations()[0], Operation::Insert { .. }));
+        assert_eq!(patch.operations()[0].query(), "b");
+    }
+
+    #[test]
+    fn test_diff_removed_non_empty_table() {
+        let patch = assert_roundtrip("a = 1\n[b.c]\nd = 2", "a = 1");
+
+        assert_eq!(
+            patch.operations(),
+            &[
+                Operation::Set {
+                    query: String::from("b"),
+                    value: Value::Table(Default::default()),
+                },
+                Operation::Delete {
+                    query: String::from("b"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_shrinking_array() {
+        let patch = assert_roundtrip("a = [1, 2, 3, 4]", "a = [1, 5]");
+
+        let queries = patch
+            .operations()
+            .iter()
+            .map(Operation::query)
+            .collect::<Vec<_>>();
+        assert_eq!(queries, vec!["a.[1]", "a.[3]", "a.[2]"]);
+    }
+
+    #[test]
+    fn test_diff_growing_array_of_tables() {
+        assert_roundtrip(
+            "[[a]]\nb = 1",
+            "[[a]]\nb = 2\n[[a]]\nb = 3\n[[a]]\nc = [[1], [2]]",
+        );
+    }
+
+    #[test]
+    fn test_diff_removed_non_empty_array_element() {
+        assert_roundtrip("a = [[1, 2], [3]]", "a = [[1, 2]]");
+    }
+
+    #[test]
+    fn test_diff_with_seperator() {
+        let from: Value = toml_from_str("[\"a.b\"]\nc = 1").unwrap();
+        let to: Value = toml_from_str("[\"a.b\"]\nc = 2").unwrap();
+
+        let err = Patch::diff(&from, &to).unwrap_err();
+        assert!(is_match!(err, Error::KeyNotQueryable(_, '.')));
+
+        let patch = Patch::diff_with_seperator(&from, &to, '/').unwrap();
+        assert_eq!(patch.operations()[0].query(), "a.b/c");
+
+        let mut patched = from.clone();
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(patched, to);
+    }
+
+    #[test]
+    fn test_diff_different_root_types() {
+        let from = Value::Table(Default::default());
+        let to = Value::Integer(1);
+
+        let err = Patch::diff(&from, &to).unwrap_err();
+        assert!(is_match!(err, Error::CannotPatchRoot("Table", "Integer")));
+    }
+
+    /// A document built from a small set of keys, so that generated documents overlap
+    #[derive(Debug, Clone)]
+    struct Document(Value);
+
+    impl Arbitrary for Document {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Document(Value::Table(arbitrary_table(g, 3)))
+        }
+    }
+
+    fn arbitrary_table<G: Gen>(g: &mut G, depth: u8) -> toml::map::Map<String, Value> {
+        let mut table = toml::map::Map::new();
+        for key in ["a", "b", "c", "d"].iter() {
+            if bool::arbitrary(g) {
+                table.insert(String::from(*key), arbitrary_value(g, depth));
+            }
+        }
+        table
+    }
+
+    fn arbitrary_value<G: Gen>(g: &mut G, depth: u8) -> Value {
+        let kinds = if depth == 0 { 4 } else { 6 };
+        match u8::arbitrary(g) % kinds {
+            0 => Value::Integer(i64::from(u8::arbitrary(g) % 3)),
+            1 => Value::Boolean(bool::arbitrary(g)),
+            2 => Value::String(String::from(if bool::arbitrary(g) { "x" } else { "y" })),
+            3 => Value::Float(f64::from(u8::arbitrary(g) % 3)),
+            4 => Value::Table(arbitrary_table(g, depth - 1)),
+            _ => {
+                let len = u8::arbitrary(g) % 4;
+                Value::Array((0..len).map(|_| arbitrary_value(g, depth - 1)).collect())
+            }
+        }
+    }
+
+    quickcheck! {
+        fn test_diff_roundtrip(from: Document, to: Document) -> bool {
+            let patch = Patch::diff(&from.0, &to.0).unwrap();
+            let mut patched = from.0.clone();
+            patched.apply_patch(&patch).unwrap();
+            patched == to.0
+        }
+
+        fn test_diff_with_itself_is_empty(doc: Document) -> bool {
+            Patch::diff(&doc.0, &doc.0).unwrap().is_empty()
+        }
+    }
+}
@@ -0,0 +1,623 @@
+//! Querying documents of other formats than TOML
+//!
+//! The query language works on every document type which implements `QueryableValue`. This
+//! crate implements it for `toml::Value` and, with the `json` and `yaml` features, for
+//! `serde_json::Value` and `serde_yaml::Value`. `QueryableExt` provides the `read`, `set`,
+//! `insert` and `delete` functions for the JSON and YAML values, with the semantics which
+//! `TomlValueReadExt`, `TomlValueSetExt`, `TomlValueInsertExt` and `TomlValueDeleteExt` have for
+//! `toml::Value`.
+
+use crate::error::{Error, Result};
+use crate::tokenizer::Token;
+
+/// The kind of a value, as far as queries are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A value with keys, which can be queried with identifiers
+    Table,
+
+    /// A value with indexes, which can be queried with indexes
+    Array,
+
+    /// Any other value
+    Other,
+}
+
+/// A document value which can be queried
+///
+/// The functions which access keys only have to work on tables, the ones which access indexes
+/// only on arrays. On other values they return `None` or do nothing.
+pub trait QueryableValue: Sized {
+    /// The kind of the value
+    fn kind(&self) -> Kind;
+
+    /// The name of the type of the value, for error messages
+    fn kind_name(&self) -> &'static str;
+
+    /// A new, empty table
+    fn empty_table() -> Self;
+
+    /// The value at `key`
+    fn get_key(&self, key: &str) -> Option<&Self>;
+
+    /// The value at `key`, mutably
+    fn get_key_mut(&mut self, key: &str) -> Option<&mut Self>;
+
+    /// Insert `value` at `key`, returning the old value at `key`
+    fn insert_key(&mut self, key: String, value: Self) -> Option<Self>;
+
+    /// Remove the value at `key`
+    fn remove_key(&mut self, key: &str) -> Option<Self>;
+
+    /// The value at `idx`
+    fn get_index(&self, idx: usize) -> Option<&Self>;
+
+    /// The value at `idx`, mutably
+    fn get_index_mut(&mut self, idx: usize) -> Option<&mut Self>;
+
+    /// Insert `value` at `idx`, which is at most the length of the array
+    fn insert_index(&mut self, idx: usize, value: Self);
+
+    /// Remove the value at `idx`
+    fn remove_index(&mut self, idx: usize) -> Option<Self>;
+
+    /// The number of values in a table or an array, `0` for other values
+    fn len(&self) -> usize;
+
+    /// Whether the value is an empty table or array, `true` for other values
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Implement the array functions of `QueryableValue` for a value with a `Vec` of values
+macro_rules! impl_array_access {
+    ($array:path) => {
+        fn get_index(&self, idx: usize) -> Option<&Self> {
+            match self {
+                $array(ref a) => a.get(idx),
+                _ => None,
+            }
+        }
+
+        fn get_index_mut(&mut self, idx: usize) -> Option<&mut Self> {
+            match self {
+                $array(ref mut a) => a.get_mut(idx),
+                _ => None,
+            }
+        }
+
+        fn insert_index(&mut self, idx: usize, value: Self) {
+            if let $array(ref mut a) = self {
+                a.insert(idx, value)
+            }
+        }
+
+        fn remove_index(&mut self, idx: usize) -> Option<Self> {
+            match self {
+                $array(ref mut a) if idx < a.len() => Some(a.remove(idx)),
+                _ => None,
+            }
+        }
+    };
+}
+
+impl QueryableValue for toml::Value {
+    fn kind(&self) -> Kind {
+        match self {
+            toml::Value::Table(_) => Kind::Table,
+            toml::Value::Array(_) => Kind::Array,
+            _ => Kind::Other,
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        crate::util::name_of_val(self)
+    }
+
+    fn empty_table() -> Self {
+        toml::Value::Table(toml::map::Map::new())
+    }
+
+    fn get_key(&self, key: &str) -> Option<&Self> {
+        self.as_table().and_then(|t| t.get(key))
+    }
+
+    fn get_key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_table_mut().and_then(|t| t.get_mut(key))
+    }
+
+    fn insert_key(&mut self, key: String, value: Self) -> Option<Self> {
+        self.as_table_mut().and_then(|t| t.insert(key, value))
+    }
+
+    fn remove_key(&mut self, key: &str) -> Option<Self> {
+        self.as_table_mut().and_then(|t| t.remove(key))
+    }
+
+    impl_array_access!(toml::Value::Array);
+
+    fn len(&self) -> usize {
+        match self {
+            toml::Value::Table(ref t) => t.len(),
+            toml::Value::Array(ref a) => a.len(),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl QueryableValue for serde_json::Value {
+    fn kind(&self) -> Kind {
+        match self {
+            serde_json::Value::Object(_) => Kind::Table,
+            serde_json::Value::Array(_) => Kind::Array,
+            _ => Kind::Other,
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self {
+            serde_json::Value::Null => "Null",
+            serde_json::Value::Bool(_) => "Boolean",
+            serde_json::Value::Number(ref n) if n.is_f64() => "Float",
+            serde_json::Value::Number(_) => "Integer",
+            serde_json::Value::String(_) => "String",
+            serde_json::Value::Array(_) => "Array",
+            serde_json::Value::Object(_) => "Object",
+        }
+    }
+
+    fn empty_table() -> Self {
+        serde_json::Value::Object(serde_json::Map::new())
+    }
+
+    fn get_key(&self, key: &str) -> Option<&Self> {
+        self.as_object().and_then(|o| o.get(key))
+    }
+
+    fn get_key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_object_mut().and_then(|o| o.get_mut(key))
+    }
+
+    fn insert_key(&mut self, key: String, value: Self) -> Option<Self> {
+        self.as_object_mut().and_then(|o| o.insert(key, value))
+    }
+
+    fn remove_key(&mut self, key: &str) -> Option<Self> {
+        self.as_object_mut().and_then(|o| o.remove(key))
+    }
+
+    impl_array_access!(serde_json::Value::Array);
+
+    fn len(&self) -> usize {
+        match self {
+            serde_json::Value::Object(ref o) => o.len(),
+            serde_json::Value::Array(ref a) => a.len(),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl QueryableValue for serde_yaml::Value {
+    fn kind(&self) -> Kind {
+        match self {
+            serde_yaml::Value::Mapping(_) => Kind::Table,
+            serde_yaml::Value::Sequence(_) => Kind::Array,
+            _ => Kind::Other,
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self {
+            serde_yaml::Value::Null => "Null",
+            serde_yaml::Value::Bool(_) => "Boolean",
+            serde_yaml::Value::Number(ref n) if n.is_f64() => "Float",
+            serde_yaml::Value::Number(_) => "Integer",
+            serde_yaml::Value::String(_) => "String",
+            serde_yaml::Value::Sequence(_) => "Sequence",
+            serde_yaml::Value::Mapping(_) => "Mapping",
+        }
+    }
+
+    fn empty_table() -> Self {
+        serde_yaml::Value::Mapping(serde_yaml::Mapping::new())
+    }
+
+    fn get_key(&self, key: &str) -> Option<&Self> {
+        let key = serde_yaml::Value::String(key.to_owned());
+        self.as_mapping().and_then(|m| m.get(&key))
+    }
+
+    fn get_key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        let key = serde_yaml::Value::String(key.to_owned());
+        self.as_mapping_mut().and_then(|m| m.get_mut(&key))
+    }
+
+    fn insert_key(&mut self, key: String, value: Self) -> Option<Self> {
+        let key = serde_yaml::Value::String(key);
+        self.as_mapping_mut().and_then(|m| m.insert(key, value))
+    }
+
+    fn remove_key(&mut self, key: &str) -> Option<Self> {
+        let key = serde_yaml::Value::String(key.to_owned());
+        self.as_mapping_mut().and_then(|m| m.remove(&key))
+    }
+
+    impl_array_access!(serde_yaml::Value::Sequence);
+
+    fn len(&self) -> usize {
+        match self {
+            serde_yaml::Value::Mapping(ref m) => m.len(),
+            serde_yaml::Value::Sequence(ref s) => s.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// Set `value` at the `last` token of a query in `val`, see `TomlValueSetExt::set`
+pub(crate) fn set_last<V: QueryableValue>(val: &mut V, last: Token, value: V) -> Result<Option<V>> {
+    match (val.kind(), last) {
+        (Kind::Table, Token::Identifier { ident, .. }) => Ok(val.insert_key(ident, value)),
+        (Kind::Array, Token::Identifier { ident, .. }) => Err(Error::NoIdentifierInArray(ident)),
+        (Kind::Other, Token::Identifier { ident, .. }) => Err(Error::QueryingValueAsTable(ident)),
+
+        (Kind::Array, Token::Index { idx, .. }) => match val.get_index_mut(idx) {
+            Some(old) => Ok(Some(std::mem::replace(old, value))),
+            None => {
+                val.insert_index(val.len(), value);
+                Ok(None)
+            }
+        },
+        (Kind::Table, Token::Index { idx, .. }) => Err(Error::NoIndexInTable(idx)),
+        (Kind::Other, Token::Index { idx, .. }) => Err(Error::QueryingValueAsArray(idx)),
+    }
+}
+
+/// Insert `value` at the `last` token of a query in `val`, see `TomlValueInsertExt::insert`
+pub(crate) fn insert_last<V: QueryableValue>(
+    val: &mut V,
+    last: Token,
+    value: V,
+) -> Result<Option<V>> {
+    match (val.kind(), last) {
+        (Kind::Table, Token::Identifier { ident, .. }) => Ok(val.insert_key(ident, value)),
+        (_, Token::Identifier { ident, .. }) => Err(Error::NoIdentifierInArray(ident)),
+
+        (Kind::Array, Token::Index { idx, .. }) => {
+            let idx = idx.min(val.len());
+            val.insert_index(idx, value);
+            Ok(None)
+        }
+        (_, Token::Index { idx, .. }) => Err(Error::NoIndexInTable(idx)),
+    }
+}
+
+/// Check that `val` is not a non-empty table or array, which is not deleted
+fn check_deletable<V: QueryableValue>(val: &V, ident: Option<String>) -> Result<()> {
+    match val.kind() {
+        Kind::Table if !val.is_empty() => Err(Error::CannotDeleteNonEmptyTable(ident)),
+        Kind::Array if !val.is_empty() => Err(Error::CannotDeleteNonEmptyArray(ident)),
+        _ => Ok(()),
+    }
+}
+
+/// Delete the value at the `last` token of a query in `val`, see `TomlValueDeleteExt::delete`
+pub(crate) fn delete_last<V: QueryableValue>(val: &mut V, last: Token) -> Result<Option<V>> {
+    match (val.kind(), last) {
+        (Kind::Table, Token::Identifier { ident, .. }) => {
+            if let Some(sub) = val.get_key(&ident) {
+                check_deletable(sub, Some(ident.clone()))?;
+            }
+            Ok(val.remove_key(&ident))
+        }
+        (Kind::Array, Token::Identifier { ident, .. }) => Err(Error::NoIdentifierInArray(ident)),
+        (Kind::Other, Token::Identifier { ident, .. }) => Err(Error::QueryingValueAsTable(ident)),
+
+        (Kind::Array, Token::Index { idx, .. }) => match val.get_index(idx) {
+            Some(sub) => {
+                check_deletable(sub, None)?;
+                Ok(val.remove_index(idx))
+            }
+            None => Err(Error::ArrayIndexOutOfBounds(idx, val.len())),
+        },
+        (Kind::Table, Token::Index { idx, .. }) => Err(Error::NoIndexInTable(idx)),
+        (Kind::Other, Token::Index { idx, .. }) => Err(Error::QueryingValueAsArray(idx)),
+    }
+}
+
+/// Extension trait for reading and modifying JSON and YAML documents
+///
+/// The functions work like the ones of `TomlValueReadExt`, `TomlValueSetExt`,
+/// `TomlValueInsertExt` and `TomlValueDeleteExt` for `toml::Value`, see their documentation.
+/// It is not implemented for `toml::Value`, which has these traits instead, so both can be
+/// imported at the same time.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "json")]
+/// # fn main() {
+/// extern crate serde_json;
+/// extern crate toml_query;
+///
+/// use toml_query::queryable::QueryableExt;
+///
+/// let mut json : serde_json::Value = serde_json::from_str(r#"{ "a": { "b": [1, 2] } }"#).unwrap();
+///
+/// json.set("a.b.[1]", serde_json::Value::from(3)).unwrap();
+/// json.insert("a.c.d", serde_json::Value::from("x")).unwrap();
+///
+/// assert_eq!(json.read("a.b.[1]").unwrap(), Some(&serde_json::Value::from(3)));
+/// assert_eq!(json.read("a.c.d").unwrap(), Some(&serde_json::Value::from("x")));
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+pub trait QueryableExt: QueryableValue {
+    /// Extension function for reading a value using a custom seperator
+    fn read_with_seperator(&self, query: &str, sep: char) -> Result<Option<&Self>>;
+
+    /// Extension function for reading a value
+    fn read(&self, query: &str) -> Result<Option<&Self>> {
+        self.read_with_seperator(query, '.')
+    }
+
+    /// Extension function for reading a value mutably using a custom seperator
+    fn read_mut_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<&mut Self>>;
+
+    /// Extension function for reading a value mutably
+    fn read_mut(&mut self, query: &str) -> Result<Option<&mut Self>> {
+        self.read_mut_with_seperator(query, '.')
+    }
+
+    /// Extension function for setting a value using a custom seperator
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Self) -> Result<Option<Self>>;
+
+    /// Extension function for setting a value
+    fn set(&mut self, query: &str, value: Self) -> Result<Option<Self>> {
+        self.set_with_seperator(query, '.', value)
+    }
+
+    /// Extension function for inserting a value using a custom seperator
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Self,
+    ) -> Result<Option<Self>>;
+
+    /// Extension function for inserting a value
+    fn insert(&mut self, query: &str, value: Self) -> Result<Option<Self>> {
+        self.insert_with_seperator(query, '.', value)
+    }
+
+    /// Extension function for deleting a value using a custom seperator
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Self>>;
+
+    /// Extension function for deleting a value
+    fn delete(&mut self, query: &str) -> Result<Option<Self>> {
+        self.delete_with_seperator(query, '.')
+    }
+}
+
+/// Implement `QueryableExt` for a type which implements `QueryableValue`
+#[cfg(any(feature = "json", feature = "yaml"))]
+macro_rules! impl_queryable_ext {
+    ($value:ty) => {
+        impl QueryableExt for $value {
+            fn read_with_seperator(&self, query: &str, sep: char) -> Result<Option<&Self>> {
+                use crate::resolver::non_mut_resolver::resolve;
+
+                let tokens = crate::tokenizer::tokenize_with_seperator(query, sep)?;
+                resolve(self, &tokens, false)
+            }
+
+            fn read_mut_with_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+            ) -> Result<Option<&mut Self>> {
+                use crate::resolver::mut_resolver::resolve;
+
+                let tokens = crate::tokenizer::tokenize_with_seperator(query, sep)?;
+                resolve(self, &tokens, false)
+            }
+
+            fn set_with_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+                value: Self,
+            ) -> Result<Option<Self>> {
+                use crate::resolver::mut_resolver::resolve;
+
+                let mut tokens = crate::tokenizer::tokenize_with_seperator(query, sep)?;
+                let (val, last) = match tokens.pop_last() {
+                    None => (self, Box::new(tokens)),
+                    // safe because of resolve() guarantees
+                    Some(last) => (resolve(self, &tokens, true)?.unwrap(), last),
+                };
+
+                set_last(val, *last, value)
+            }
+
+            fn insert_with_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+                value: Self,
+            ) -> Result<Option<Self>> {
+                use crate::resolver::mut_creating_resolver::resolve;
+
+                let mut tokens = crate::tokenizer::tokenize_with_seperator(query, sep)?;
+                let (val, last) = match tokens.pop_last() {
+                    None => (self, Box::new(tokens)),
+                    Some(last) => (resolve(self, &tokens)?, last),
+                };
+
+                insert_last(val, *last, value)
+            }
+
+            fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Self>> {
+                use crate::resolver::mut_resolver::resolve;
+
+                let mut tokens = crate::tokenizer::tokenize_with_seperator(query, sep)?;
+                let (val, last) = match tokens.pop_last() {
+                    None => (self, Box::new(tokens)),
+                    // safe because of resolve() guarantees
+                    Some(last) => (resolve(self, &tokens, true)?.unwrap(), last),
+                };
+
+                delete_last(val, *last)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "json")]
+impl_queryable_ext!(serde_json::Value);
+
+#[cfg(feature = "yaml")]
+impl_queryable_ext!(serde_yaml::Value);
+
+#[cfg(test)]
+mod test {
+    #[cfg(any(feature = "json", feature = "yaml"))]
+    use super::*;
+
+    #[test]
+    fn test_toml_with_both_traits_imported() {
+        #[allow(unused_imports)]
+        use crate::queryable::QueryableExt;
+        use crate::read::TomlValueReadExt;
+
+        let toml: toml::Value = toml::from_str("[a]\nb = 1").unwrap();
+
+        assert_eq!(toml.read("a.b").unwrap(), Some(&toml::Value::Integer(1)));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        use serde_json::{json, Value};
+
+        let mut doc = json!({ "a": { "b": [1, 2] }, "s": "x" });
+
+        assert_eq!(doc.read("a.b.[1]").unwrap(), Some(&json!(2)));
+        assert_eq!(doc.read("a.c").unwrap(), None);
+        assert_eq!(doc.set("a.b.[0]", json!(0)).unwrap(), Some(json!(1)));
+        assert_eq!(doc.insert("c.d.e", json!(null)).unwrap(), None);
+        assert_eq!(doc.delete("a.b.[1]").unwrap(), Some(json!(2)));
+        *doc.read_mut("s").unwrap().unwrap() = json!("y");
+
+        assert_eq!(
+            doc,
+            json!({ "a": { "b": [0] }, "c": { "d": { "e": null } }, "s": "y" })
+        );
+        assert!(is_match!(
+            doc.read("s.x"),
+            Err(Error::QueryingValueAsTable(_))
+        ));
+        assert!(is_match!(
+            doc.set("a.b.x", Value::Null),
+            Err(Error::NoIdentifierInArray(_))
+        ));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml() {
+        use serde_yaml::Value;
+
+        let mut doc: Value = serde_yaml::from_str("a:\n  b:\n    - 1\n    - 2\n").unwrap();
+
+        assert_eq!(doc.read("a.b.[1]").unwrap(), Some(&Value::from(2)));
+        doc.set("a.b.[0]", Value::from("zero")).unwrap();
+        doc.insert("c.d", Value::from(true)).unwrap();
+        assert!(is_match!(
+            doc.delete("a.b"),
+            Err(Error::CannotDeleteNonEmptyArray(_))
+        ));
+
+        let expected: Value =
+            serde_yaml::from_str("a:\n  b:\n    - zero\n    - 2\nc:\n  d: true\n").unwrap();
+        assert_eq!(doc, expected);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_errors() {
+        use serde_json::json;
+
+        let mut doc = json!({ "a": { "b": [1, 2] }, "s": "x" });
+        let before = doc.clone();
+
+        assert!(is_match!(
+            doc.delete("a"),
+            Err(Error::CannotDeleteNonEmptyTable(_))
+        ));
+        assert!(is_match!(
+            doc.delete("a.b"),
+            Err(Error::CannotDeleteNonEmptyArray(_))
+        ));
+        assert!(is_match!(
+            doc.read("a.b.[2].c"),
+            Err(Error::IndexOutOfBounds(2, 2))
+        ));
+        assert!(is_match!(
+            doc.delete("a.b.[2]"),
+            Err(Error::ArrayIndexOutOfBounds(2, 2))
+        ));
+
+        // Like with toml::Value, only tables can be inserted into
+        assert!(is_match!(
+            doc.insert("s.x", json!(1)),
+            Err(Error::NoIdentifierInArray(_))
+        ));
+        assert!(is_match!(
+            doc.insert("s.x.y", json!(1)),
+            Err(Error::QueryingValueAsTable(_))
+        ));
+        assert_eq!(doc, before);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_errors() {
+        use serde_yaml::Value;
+
+        let mut doc: Value = serde_yaml::from_str("a:\n  b:\n    - 1\n    - 2\ns: x\n").unwrap();
+        let before = doc.clone();
+
+        assert!(is_match!(
+            doc.delete("a"),
+            Err(Error::CannotDeleteNonEmptyTable(_))
+        ));
+        assert!(is_match!(
+            doc.delete("a.b"),
+            Err(Error::CannotDeleteNonEmptyArray(_))
+        ));
+        assert!(is_match!(
+            doc.read("a.b.[2].c"),
+            Err(Error::IndexOutOfBounds(2, 2))
+        ));
+        assert!(is_match!(
+            doc.delete("a.b.[2]"),
+            Err(Error::ArrayIndexOutOfBounds(2, 2))
+        ));
+
+        // Like with toml::Value, only tables can be inserted into
+        assert!(is_match!(
+            doc.insert("s.x", Value::from(1)),
+            Err(Error::NoIdentifierInArray(_))
+        ));
+        assert!(is_match!(
+            doc.insert("s.x.y", Value::from(1)),
+            Err(Error::QueryingValueAsTable(_))
+        ));
+
+        assert_eq!(doc, before);
+    }
+}
@@ -1,4 +1,4 @@
-/// The Toml Read extensions
+//! The Toml Read extensions
 
 #[cfg(feature = "typed")]
 use std::fmt::Debug;
@@ -7,7 +7,13 @@ use std::fmt::Debug;
 use serde::{Deserialize, Serialize};
 use toml::Value;
 
+#[cfg(feature = "typed")]
+use crate::delete::TomlValueDeleteExt;
 use crate::error::{Error, Result};
+#[cfg(feature = "typed")]
+use crate::insert::TomlValueInsertExt;
+#[cfg(feature = "typed")]
+use crate::set::TomlValueSetExt;
 use crate::tokenizer::tokenize_with_seperator;
 
 pub trait TomlValueReadExt<'doc> {
@@ -34,21 +40,84 @@ pub trait TomlValueReadExt<'doc> {
     }
 
     #[cfg(feature = "typed")]
-    fn read_deserialized<'de, D: Deserialize<'de>>(&'doc self, query: &str) -> Result<Option<D>> {
+    fn read_deserialized<D: Deserialize<'doc>>(&'doc self, query: &str) -> Result<Option<D>> {
+        use crate::deserializer::ValueDeserializer;
+
         let raw = self.read(query)?;
 
         match raw {
             Some(value) => {
-                let deserialized = value.clone().try_into().map_err(Error::TomlDeserialize)?;
+                let deserialized = D::deserialize(ValueDeserializer::new(value))
+                    .map_err(Error::TomlDeserialize)?;
                 Ok(Some(deserialized))
             }
             None => Ok(None),
         }
     }
 
+    /// Extension function for deserializing a value from the current toml::Value document and
+    /// finding the keys the deserialized type does not use
+    ///
+    /// # Return value
+    ///
+    /// * If there is a value at `query`, `Ok(Some((value, unused)))` is returned, where `unused`
+    ///   holds the queries of all values below `query` which were ignored while deserializing.
+    /// * If there is no value at `query`, `Ok(None)` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query cannot be resolved (see `TomlValueReadExt`): error
+    ///     * If the value cannot be deserialized: error
+    ///     * If the key of an unused value cannot be expressed in a query: error
+    ///
+    #[cfg(feature = "typed")]
+    fn read_deserialized_with_unused<D: Deserialize<'doc>>(
+        &'doc self,
+        query: &str,
+    ) -> Result<Option<(D, Vec<String>)>> {
+        use crate::deserializer::{Unused, ValueDeserializer};
+        use crate::util::{path_to_query, token_path};
+
+        let raw = self.read(query)?;
+
+        match raw {
+            Some(value) => {
+                let path = token_path(&tokenize_with_seperator(query, '.')?);
+                let unused = Unused::default();
+                let deserialized =
+                    D::deserialize(ValueDeserializer::tracking(value, path, &unused))
+                        .map_err(Error::TomlDeserialize)?;
+
+                let unused = unused
+                    .borrow()
+                    .iter()
+                    .map(|path| path_to_query(path, '.'))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Some((deserialized, unused)))
+            }
+            None => Ok(None),
+        }
+    }
+
     #[cfg(feature = "typed")]
-    fn read_partial<'a, P: Partial<'a>>(&'doc self) -> Result<Option<P::Output>> {
-        self.read_deserialized::<P::Output>(P::LOCATION)
+    fn read_partial<P: Partial<'doc>>(&'doc self) -> Result<Option<P::Output>> {
+        P::read_from(self)
+    }
+
+    /// Extension function for reading a `Partial` which uses all keys at its location
+    ///
+    /// The `Partial` is read with `Partial::read_strict_from`, which by default checks the keys
+    /// in the table at `P::LOCATION`.
+    ///
+    /// # Return value
+    ///
+    /// * On success, see `TomlValueReadExt::read_partial`.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If `TomlValueReadExt::read_deserialized_with_unused` fails: error
+    ///     * If the `Partial` does not use some of the keys: `Error::UnusedKeys` with their
+    ///       queries
+    ///
+    #[cfg(feature = "typed")]
+    fn read_partial_strict<P: Partial<'doc>>(&'doc self) -> Result<Option<P::Output>> {
+        P::read_strict_from(self)
     }
 }
 
@@ -60,6 +129,244 @@ pub trait Partial<'a> {
 
     // The type which represents the data
     type Output: Serialize + Deserialize<'a> + Debug;
+
+    /// Read the data from `doc`
+    ///
+    /// By default, the value at `LOCATION` is deserialized. `#[derive(Partial)]` reads types
+    /// with field locations field by field.
+    fn read_from<R>(doc: &'a R) -> Result<Option<Self::Output>>
+    where
+        R: TomlValueReadExt<'a> + ?Sized,
+    {
+        doc.read_deserialized(Self::LOCATION)
+    }
+
+    /// Read the data from `doc`, failing with `Error::UnusedKeys` if it does not use all keys
+    ///
+    /// By default, the value at `LOCATION` is deserialized and all keys below it must be used.
+    /// `#[derive(Partial)]` checks types with field locations field by field, and the keys in
+    /// the struct's location must belong to one of the fields.
+    fn read_strict_from<R>(doc: &'a R) -> Result<Option<Self::Output>>
+    where
+        R: TomlValueReadExt<'a> + ?Sized,
+    {
+        match doc.read_deserialized_with_unused::<Self::Output>(Self::LOCATION)? {
+            Some((_, unused)) if !unused.is_empty() => Err(Error::UnusedKeys(unused)),
+            Some((partial, _)) => Ok(Some(partial)),
+            None => Ok(None),
+        }
+    }
+
+    /// Write the data to `doc`, creating missing tables
+    ///
+    /// By default, the serialized data is set at `LOCATION` like with
+    /// `TomlValueSetExt::set_or_create` and the old value there is returned.
+    fn set_in<S>(data: &Self::Output, doc: &mut S) -> Result<Option<Value>>
+    where
+        S: TomlValueSetExt + TomlValueDeleteExt + ?Sized,
+    {
+        let value = Value::try_from(data).map_err(Error::TomlSerialize)?;
+        doc.set_or_create(Self::LOCATION, value)
+    }
+
+    /// Insert the data into `doc`
+    ///
+    /// By default, the serialized data is inserted at `LOCATION` like with
+    /// `TomlValueInsertExt::insert`.
+    fn insert_in<I>(data: &Self::Output, doc: &mut I) -> Result<Option<Value>>
+    where
+        I: TomlValueInsertExt + TomlValueDeleteExt + ?Sized,
+    {
+        let value = Value::try_from(data).map_err(Error::TomlSerialize)?;
+        doc.insert(Self::LOCATION, value)
+    }
+}
+
+/// Helpers for the code `#[derive(Partial)]` generates for types with field locations
+#[cfg(feature = "typed")]
+#[doc(hidden)]
+pub mod partial_fields {
+    use serde::{Deserialize, Serialize};
+    pub use toml::map::Map;
+    pub use toml::Value;
+
+    use crate::delete::TomlValueDeleteExt;
+    use crate::error::{Error, Result};
+    use crate::insert::TomlValueInsertExt;
+    use crate::read::TomlValueReadExt;
+    use crate::set::TomlValueSetExt;
+    use crate::tokenizer::tokenize_with_seperator;
+    use crate::util::{path_to_query, token_path, Segment};
+
+    /// Read the field at `query`, remembering in `found` whether it exists
+    pub fn read<'a, R, T>(doc: &'a R, query: &str, found: &mut bool) -> Result<Option<T>>
+    where
+        R: TomlValueReadExt<'a> + ?Sized,
+        T: Deserialize<'a>,
+    {
+        let value = doc.read_deserialized(query)?;
+        *found |= value.is_some();
+        Ok(value)
+    }
+
+    /// Read the field at `query` like `read`, collecting the keys it does not use in `unused`
+    pub fn read_strict<'a, R, T>(
+        doc: &'a R,
+        query: &str,
+        found: &mut bool,
+        unused: &mut Vec<String>,
+    ) -> Result<Option<T>>
+    where
+        R: TomlValueReadExt<'a> + ?Sized,
+        T: Deserialize<'a>,
+    {
+        match doc.read_deserialized_with_unused(query)? {
+            Some((value, field_unused)) => {
+                *found = true;
+                unused.extend(field_unused);
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Collect the keys in the table at `location` which neither belong to nor lead to one of
+    /// the fields at `queries` in `unused`
+    ///
+    /// Nothing is checked without a location, as the document root holds other data as well.
+    pub fn unclaimed<'a, R>(
+        doc: &'a R,
+        location: &str,
+        queries: &[&str],
+        unused: &mut Vec<String>,
+    ) -> Result<()>
+    where
+        R: TomlValueReadExt<'a> + ?Sized,
+    {
+        if location.is_empty() {
+            return Ok(());
+        }
+
+        let fields = queries
+            .iter()
+            .map(|query| tokenize_with_seperator(query, '.').map(|t| token_path(&t)))
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(value) = doc.read(location)? {
+            let mut path = token_path(&tokenize_with_seperator(location, '.')?);
+            collect_unclaimed(value, &mut path, &fields, unused)?;
+        }
+        Ok(())
+    }
+
+    fn collect_unclaimed(
+        value: &Value,
+        path: &mut Vec<Segment>,
+        fields: &[Vec<Segment>],
+        unused: &mut Vec<String>,
+    ) -> Result<()> {
+        if fields.iter().any(|field| field == path) {
+            return Ok(());
+        }
+
+        match value {
+            // Tables on the way to a field may hold other keys, which are checked as well
+            Value::Table(ref table) if fields.iter().any(|field| field.starts_with(path)) => {
+                for (key, value) in table.iter() {
+                    path.push(Segment::Key(key.clone()));
+                    collect_unclaimed(value, path, fields, unused)?;
+                    path.pop();
+                }
+            }
+            _ => unused.push(path_to_query(path, '.')?),
+        }
+        Ok(())
+    }
+
+    /// Get a field which is not optional
+    pub fn required<T>(value: Option<T>, query: &str) -> Result<T> {
+        value.ok_or_else(|| Error::NotAvailable(query.to_owned()))
+    }
+
+    /// Serialize a field, `None` if it is a missing value
+    fn serialize<T: Serialize>(value: &T) -> Result<Option<Value>> {
+        match Value::try_from(value) {
+            Ok(value) => Ok(Some(value)),
+            Err(toml::ser::Error::UnsupportedNone) => Ok(None),
+            Err(e) => Err(Error::TomlSerialize(e)),
+        }
+    }
+
+    /// Delete the value of a missing field at `query`, if there is one, and the tables which
+    /// become empty because of it
+    fn delete<D>(doc: &mut D, query: &str) -> Result<Option<Value>>
+    where
+        D: TomlValueDeleteExt + ?Sized,
+    {
+        match doc.delete_recursive_and_prune(query) {
+            Err(Error::IdentifierNotFoundInDocument(_)) => Ok(None),
+            other => other,
+        }
+    }
+
+    /// Set the field `name` at `query`, collecting the old value in `old`
+    ///
+    /// Missing values are deleted, like they are missing in the table the struct serializes to,
+    /// and so are the tables which only contained them.
+    pub fn set<S, T>(
+        doc: &mut S,
+        query: &str,
+        name: &str,
+        value: &T,
+        old: &mut Map<String, Value>,
+    ) -> Result<()>
+    where
+        S: TomlValueSetExt + TomlValueDeleteExt + ?Sized,
+        T: Serialize,
+    {
+        let old_value = match serialize(value)? {
+            Some(value) => doc.set_or_create(query, value)?,
+            None => delete(doc, query)?,
+        };
+        if let Some(old_value) = old_value {
+            old.insert(name.to_owned(), old_value);
+        }
+        Ok(())
+    }
+
+    /// Insert the field `name` at `query`, collecting the old value in `old`
+    ///
+    /// Missing values are deleted, like they are missing in the table the struct serializes to,
+    /// and so are the tables which only contained them.
+    pub fn insert<I, T>(
+        doc: &mut I,
+        query: &str,
+        name: &str,
+        value: &T,
+        old: &mut Map<String, Value>,
+    ) -> Result<()>
+    where
+        I: TomlValueInsertExt + TomlValueDeleteExt + ?Sized,
+        T: Serialize,
+    {
+        let old_value = match serialize(value)? {
+            Some(value) => doc.insert(query, value)?,
+            None => delete(doc, query)?,
+        };
+        if let Some(old_value) = old_value {
+            old.insert(name.to_owned(), old_value);
+        }
+        Ok(())
+    }
+
+    /// The old values of the fields, as a table of the fields which had one
+    pub fn old(old: Map<String, Value>) -> Option<Value> {
+        if old.is_empty() {
+            None
+        } else {
+            Some(Value::Table(old))
+        }
+    }
 }
 
 impl<'doc> TomlValueReadExt<'doc> for Value {
@@ -92,8 +399,8 @@ macro_rules! make_type_getter {
         fn $fnname(&'doc self, query: &str) -> Result<Option<$rettype>> {
             self.read_with_seperator(query, '.').and_then(|o| match o {
                 $matcher => Ok(Some($implementation)),
-                Some(o)  => Err(Error::TypeError($typename, crate::util::name_of_val(&o)).into()),
-                None     => Ok(None),
+                Some(o) => Err(Error::TypeError($typename, crate::util::name_of_val(&o)).into()),
+                None => Ok(None),
             })
         }
     };
@@ -103,10 +410,10 @@ impl<'doc, T> TomlValueReadTypeExt<'doc> for T
 where
     T: TomlValueReadExt<'doc>,
 {
-    make_type_getter!(read_string, String, "String", Some(&Value::String(ref obj)) => obj.clone());
-    make_type_getter!(read_int, i64, "Integer", Some(&Value::Integer(obj)) => obj);
-    make_type_getter!(read_float, f64, "Float", Some(&Value::Float(obj)) => obj);
-    make_type_getter!(read_bool, bool, "Boolean", Some(&Value::Boolean(obj)) => obj);
+    make_type_getter!(read_string, String, "String", Some(Value::String(obj)) => obj.clone());
+    make_type_getter!(read_int, i64, "Integer", Some(Value::Integer(obj)) => *obj);
+    make_type_getter!(read_float, f64, "Float", Some(Value::Float(obj)) => *obj);
+    make_type_getter!(read_bool, bool, "Boolean", Some(Value::Boolean(obj)) => *obj);
 }
 
 #[cfg(test)]
@@ -358,8 +665,29 @@ mod high_level_fn_test {
             .unwrap()
             .is_none());
         let _: Test = toml.read_deserialized("table.value").unwrap().unwrap();
+    }
+
+    #[cfg(feature = "typed")]
+    #[test]
+    fn test_deser_borrowed() {
+        #[derive(Deserialize, Debug)]
+        struct Test<'a> {
+            name: &'a str,
+            tags: Vec<&'a str>,
+        }
 
-        assert!(true);
+        let toml: Value = toml_from_str(
+            r#"
+        [table]
+        name = "foo"
+        tags = ["a", "b"]
+        "#,
+        )
+        .unwrap();
+
+        let test: Test<'_> = toml.read_deserialized("table").unwrap().unwrap();
+        assert_eq!(test.name, "foo");
+        assert_eq!(test.tags, vec!["a", "b"]);
     }
 }
 
@@ -395,4 +723,246 @@ mod partial_tests {
         let obj: TestObj = tbl.read_partial::<TestObj>().unwrap().unwrap();
         assert_eq!(obj.value, "foobar");
     }
+
+    #[test]
+    fn test_read_deserialized_with_unused() {
+        #[derive(Debug, Deserialize)]
+        struct Server {
+            port: u16,
+            backends: Vec<Backend>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Backend {
+            host: String,
+        }
+
+        let toml: Value = toml::from_str(
+            r#"
+        [server]
+        prot = 80
+        port = 80
+        backends = [{ host = "a" }, { host = "b", weight = 2 }]
+        "#,
+        )
+        .unwrap();
+
+        let (server, unused) = toml
+            .read_deserialized_with_unused::<Server>("server")
+            .unwrap()
+            .unwrap();
+        assert_eq!(server.port, 80);
+        assert_eq!(server.backends[1].host, "b");
+        assert_eq!(unused, vec!["server.backends.[1].weight", "server.prot"]);
+
+        assert!(toml
+            .read_deserialized_with_unused::<Server>("client")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_read_partial_strict() {
+        use crate::insert::TomlValueInsertExt;
+
+        let mut toml = Value::Table(Map::new());
+        toml.insert("foo.value", Value::String(String::from("foobar")))
+            .unwrap();
+        let obj = toml.read_partial_strict::<TestObj>().unwrap().unwrap();
+        assert_eq!(obj.value, "foobar");
+
+        toml.insert("foo.other", Value::Integer(1)).unwrap();
+        match toml.read_partial_strict::<TestObj>() {
+            Err(Error::UnusedKeys(keys)) => assert_eq!(keys, vec!["foo.other"]),
+            other => panic!("Expected unused keys, got {:?}", other),
+        }
+    }
+
+    mod derived {
+        use crate::error::Error;
+        use crate::insert::TomlValueInsertExt;
+        use crate::read::TomlValueReadExt;
+        use crate::set::TomlValueSetExt;
+        use toml::Value;
+        use toml_query_derive::Partial;
+
+        #[derive(Debug, Deserialize, Serialize, Partial, PartialEq)]
+        #[location = "server"]
+        struct Server {
+            host: String,
+            #[location = ".listen.port"]
+            port: u16,
+            #[toml_query(path = "log.level")]
+            log_level: Option<String>,
+        }
+
+        #[test]
+        fn test_read_field_locations() {
+            let toml: Value = toml::from_str(
+                r#"
+            [server]
+            host = "localhost"
+            [server.listen]
+            port = 80
+            "#,
+            )
+            .unwrap();
+            let server = toml.read_partial::<Server>().unwrap().unwrap();
+            assert_eq!(
+                server,
+                Server {
+                    host: String::from("localhost"),
+                    port: 80,
+                    log_level: None,
+                }
+            );
+
+            let empty: Value = toml::from_str("").unwrap();
+            assert!(empty.read_partial::<Server>().unwrap().is_none());
+
+            let incomplete: Value = toml::from_str("[server]\nhost = \"a\"").unwrap();
+            let err = incomplete.read_partial::<Server>().unwrap_err();
+            assert!(is_match!(err, Error::NotAvailable(ref q) if q == "server.listen.port"));
+        }
+
+        #[test]
+        fn test_write_field_locations() {
+            let server = Server {
+                host: String::from("example.com"),
+                port: 443,
+                log_level: Some(String::from("debug")),
+            };
+
+            let mut toml: Value =
+                toml::from_str("[server]\nhost = \"localhost\"\n[server.listen]\nport = 80")
+                    .unwrap();
+            let old = toml.set_partial(&server).unwrap().unwrap();
+            assert_eq!(old.get("port"), Some(&Value::Integer(80)));
+            assert_eq!(old.get("log_level"), None);
+            assert_eq!(toml.read_partial::<Server>().unwrap().unwrap(), server);
+            assert_eq!(
+                toml.read("log.level").unwrap(),
+                Some(&Value::String(String::from("debug")))
+            );
+
+            let mut toml: Value = toml::from_str("").unwrap();
+            assert!(toml.insert_partial(&server).unwrap().is_none());
+            assert_eq!(toml.read_partial::<Server>().unwrap().unwrap(), server);
+        }
+
+        #[test]
+        fn test_write_field_locations_deletes_missing_fields() {
+            let server = Server {
+                host: String::from("localhost"),
+                port: 80,
+                log_level: None,
+            };
+
+            let mut toml: Value =
+                toml::from_str("[server]\nhost = \"a\"\n[log]\nlevel = \"info\"").unwrap();
+            let old = toml.set_partial(&server).unwrap().unwrap();
+            assert_eq!(
+                old.get("log_level"),
+                Some(&Value::String(String::from("info")))
+            );
+            assert_eq!(toml.read("log").unwrap(), None);
+            assert_eq!(toml.read_partial::<Server>().unwrap().unwrap(), server);
+
+            let mut toml: Value = toml::from_str("[log]\nlevel = \"info\"").unwrap();
+            toml.insert_partial(&server).unwrap();
+            assert_eq!(toml.read("log").unwrap(), None);
+        }
+
+        #[test]
+        fn test_read_strict_field_locations() {
+            let toml: Value = toml::from_str(
+                r#"
+            [server]
+            host = "localhost"
+            [server.listen]
+            port = 80
+            [log]
+            level = "info"
+            other = 1
+            "#,
+            )
+            .unwrap();
+            let server = toml.read_partial_strict::<Server>().unwrap().unwrap();
+            assert_eq!(server.log_level, Some(String::from("info")));
+
+            let toml: Value = toml::from_str(
+                r#"
+            [server]
+            host = "localhost"
+            hots = "localhost"
+            [server.listen]
+            port = 80
+            backlog = 10
+            "#,
+            )
+            .unwrap();
+            match toml.read_partial_strict::<Server>() {
+                Err(Error::UnusedKeys(keys)) => {
+                    assert_eq!(keys, vec!["server.hots", "server.listen.backlog"])
+                }
+                other => panic!("Expected unused keys, got {:?}", other),
+            }
+
+            let empty: Value = toml::from_str("").unwrap();
+            assert!(empty.read_partial_strict::<Server>().unwrap().is_none());
+        }
+
+        #[derive(Debug, Deserialize, Serialize, Partial, PartialEq)]
+        #[location = "client"]
+        struct Client {
+            #[serde(rename = "addr")]
+            address: String,
+            #[serde(default)]
+            retries: u32,
+            #[serde(default = "default_timeout")]
+            timeout: u64,
+            #[serde(skip)]
+            connections: Vec<String>,
+            #[location = ".tls.enabled"]
+            tls: bool,
+        }
+
+        fn default_timeout() -> u64 {
+            30
+        }
+
+        #[test]
+        fn test_field_locations_with_serde_attributes() {
+            let mut toml: Value =
+                toml::from_str("[client]\naddr = \"a\"\n[client.tls]\nenabled = true").unwrap();
+            let client = toml.read_partial_strict::<Client>().unwrap().unwrap();
+            assert_eq!(
+                client,
+                Client {
+                    address: String::from("a"),
+                    retries: 0,
+                    timeout: 30,
+                    connections: vec![],
+                    tls: true,
+                }
+            );
+
+            let client = Client {
+                address: String::from("b"),
+                connections: vec![String::from("c")],
+                ..client
+            };
+            let old = toml.set_partial(&client).unwrap().unwrap();
+            assert_eq!(old.get("addr"), Some(&Value::String(String::from("a"))));
+            assert_eq!(
+                toml.read("client.addr").unwrap(),
+                Some(&Value::String(String::from("b")))
+            );
+            assert_eq!(
+                toml.read("client.retries").unwrap(),
+                Some(&Value::Integer(0))
+            );
+            assert_eq!(toml.read("client.connections").unwrap(), None);
+        }
+    }
 }
@@ -1,3 +1,4 @@
+pub mod multi_resolver;
 pub mod mut_creating_resolver;
 pub mod mut_resolver;
 pub mod non_mut_resolver;
@@ -0,0 +1,281 @@
+/// The query resolver that finds all values in the TOML object matching a pattern
+use crate::tokenizer::Token;
+use crate::util::Segment;
+use toml::Value;
+
+/// Identifier which matches any single key or index
+pub const WILDCARD: &str = "*";
+
+/// Identifier which matches any number of keys or indexes, including none
+pub const RECURSIVE_WILDCARD: &str = "**";
+
+/// Resolves the pattern in the passed document and returns the paths of all matching values
+///
+/// # Guarantees
+///
+/// The paths are sorted, so a path comes before the paths of all values inside of it and
+/// array elements are ordered by their index. No path is returned twice.
+///
+/// Parts of the pattern which do not fit the structure of the document are no error, they just
+/// do not match. The document root itself never matches.
+///
+pub fn resolve(toml: &Value, tokens: &Token) -> Vec<Vec<Segment>> {
+    let mut matches = vec![];
+    walk(toml, Some(tokens), &mut vec![], &mut matches);
+    matches.retain(|path| !path.is_empty());
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+/// Like `resolve()`, but leaves out all paths which are inside of another matching path
+pub fn resolve_outermost(toml: &Value, tokens: &Token) -> Vec<Vec<Segment>> {
+    let mut outermost: Vec<Vec<Segment>> = vec![];
+    for path in resolve(toml, tokens) {
+        match outermost.last() {
+            Some(last) if path.starts_with(last) => {}
+            _ => outermost.push(path),
+        }
+    }
+    outermost
+}
+
+/// Check whether the path of a value matches the pattern, without looking at a document
+///
+/// Like with `resolve()`, the document root itself never matches.
+pub fn matches(tokens: &Token, path: &[Segment]) -> bool {
+    !path.is_empty() && matches_from(Some(tokens), path)
+}
+
+fn matches_from(token: Option<&Token>, path: &[Segment]) -> bool {
+    let token = match token {
+        None => return path.is_empty(),
+        Some(token) => token,
+    };
+
+    match (token, path.split_first()) {
+        (Token::Identifier { ref ident, .. }, rest) if ident == RECURSIVE_WILDCARD => {
+            matches_from(token.next(), path)
+                || rest
+                    .into_iter()
+                    .any(|(_, rest)| matches_from(Some(token), rest))
+        }
+        (Token::Identifier { ref ident, .. }, Some((_, rest))) if ident == WILDCARD => {
+            matches_from(token.next(), rest)
+        }
+        (Token::Identifier { ref ident, .. }, Some((Segment::Key(ref key), rest))) => {
+            ident == key && matches_from(token.next(), rest)
+        }
+        (Token::Index { idx, .. }, Some((Segment::Index(i), rest))) => {
+            idx == i && matches_from(token.next(), rest)
+        }
+        _ => false,
+    }
+}
+
+fn children(toml: &Value) -> Vec<(Segment, &Value)> {
+    match toml {
+        Value::Table(ref t) => t
+            .iter()
+            .map(|(k, v)| (Segment::Key(k.clone()), v))
+            .collect(),
+        Value::Array(ref a) => a
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Segment::Index(i), v))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn walk(
+    toml: &Value,
+    token: Option<&Token>,
+    path: &mut Vec<Segment>,
+    matches: &mut Vec<Vec<Segment>>,
+) {
+    let token = match token {
+        None => return matches.push(path.clone()),
+        Some(token) => token,
+    };
+
+    match token {
+        Token::Identifier { ref ident, .. } if ident == RECURSIVE_WILDCARD => {
+            walk(toml, token.next(), path, matches);
+            for (segment, child) in children(toml) {
+                path.push(segment);
+                walk(child, Some(token), path, matches);
+                path.pop();
+            }
+        }
+
+        Token::Identifier { ref ident, .. } if ident == WILDCARD => {
+            for (segment, child) in children(toml) {
+                path.push(segment);
+                walk(child, token.next(), path, matches);
+                path.pop();
+            }
+        }
+
+        Token::Identifier { ref ident, .. } => {
+            if let Value::Table(ref t) = toml {
+                if let Some(child) = t.get(ident) {
+                    path.push(Segment::Key(ident.clone()));
+                    walk(child, token.next(), path, matches);
+                    path.pop();
+                }
+            }
+        }
+
+        Token::Index { idx, .. } => {
+            if let Value::Array(ref a) = toml {
+                if let Some(child) = a.get(*idx) {
+                    path.push(Segment::Index(*idx));
+                    walk(child, token.next(), path, matches);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{matches, resolve, resolve_outermost};
+    use crate::tokenizer::*;
+    use crate::util::Segment;
+    use toml::from_str as toml_from_str;
+    use toml::Value;
+
+    macro_rules! do_resolve {
+        ( $toml:ident => $query:expr ) => {
+            resolve(
+                &$toml,
+                &tokenize_with_seperator(&String::from($query), '.').unwrap(),
+            )
+        };
+    }
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(String::from(k))
+    }
+
+    #[test]
+    fn test_resolve_plain_query() {
+        let toml: Value = toml_from_str("[a]\nb = 1").unwrap();
+
+        assert_eq!(do_resolve!(toml => "a.b"), vec![vec![key("a"), key("b")]]);
+        assert!(do_resolve!(toml => "a.c").is_empty());
+        assert!(do_resolve!(toml => "a.b.c").is_empty());
+        assert!(do_resolve!(toml => "a.[0]").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_wildcard() {
+        let toml: Value = toml_from_str(
+            r#"
+            [plugins.a]
+            enabled = true
+            [plugins.b]
+            name = "b"
+            [plugins.c]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+
+        let result = do_resolve!(toml => "plugins.*.enabled");
+
+        assert_eq!(
+            result,
+            vec![
+                vec![key("plugins"), key("a"), key("enabled")],
+                vec![key("plugins"), key("c"), key("enabled")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_wildcard_in_array() {
+        let toml: Value = toml_from_str("a = [[1, 2], [3]]").unwrap();
+
+        let result = do_resolve!(toml => "a.*.[1]");
+
+        assert_eq!(
+            result,
+            vec![vec![key("a"), Segment::Index(0), Segment::Index(1)]]
+        );
+    }
+
+    #[test]
+    fn test_resolve_recursive_wildcard() {
+        let toml: Value = toml_from_str(
+            r#"
+            password = "a"
+            [db]
+            password = "b"
+            [[servers]]
+            password = "c"
+            [[servers]]
+            name = "d"
+            "#,
+        )
+        .unwrap();
+
+        let result = do_resolve!(toml => "**.password");
+
+        assert_eq!(
+            result,
+            vec![
+                vec![key("db"), key("password")],
+                vec![key("password")],
+                vec![key("servers"), Segment::Index(0), key("password")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_outermost() {
+        let toml: Value = toml_from_str("[a]\nb = 1\n[a.a]\nc = 2").unwrap();
+        let tokens = tokenize_with_seperator("**.a", '.').unwrap();
+
+        assert_eq!(
+            resolve(&toml, &tokens),
+            vec![vec![key("a")], vec![key("a"), key("a")]]
+        );
+        assert_eq!(resolve_outermost(&toml, &tokens), vec![vec![key("a")]]);
+    }
+
+    #[test]
+    fn test_resolve_never_matches_root() {
+        let toml: Value = toml_from_str("a = 1").unwrap();
+
+        assert_eq!(do_resolve!(toml => "**"), vec![vec![key("a")]]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let path = vec![
+            Segment::Key(String::from("a")),
+            Segment::Index(0),
+            Segment::Key(String::from("b")),
+        ];
+        let is_match = |pattern: &str| {
+            let tokens = tokenize_with_seperator(&String::from(pattern), '.').unwrap();
+            matches(&tokens, &path)
+        };
+
+        assert!(is_match("a.[0].b"));
+        assert!(is_match("a.*.b"));
+        assert!(is_match("**.b"));
+        assert!(is_match("a.**"));
+        assert!(is_match("**"));
+        assert!(!is_match("a.*"));
+        assert!(!is_match("a.[1].b"));
+        assert!(!is_match("*.b"));
+        assert!(!matches(
+            &tokenize_with_seperator(&String::from("**"), '.').unwrap(),
+            &[]
+        ));
+    }
+}
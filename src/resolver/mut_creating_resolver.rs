@@ -1,9 +1,9 @@
 use crate::error::{Error, Result};
-use crate::tokenizer::Token;
+use crate::queryable::{Kind, QueryableValue};
 /// The query resolver that operates on the AST and the TOML object
-use toml::{map::Map, Value};
+use crate::tokenizer::Token;
 
-pub fn resolve<'doc>(toml: &'doc mut Value, tokens: &Token) -> Result<&'doc mut Value> {
+pub fn resolve<'doc, V: QueryableValue>(toml: &'doc mut V, tokens: &Token) -> Result<&'doc mut V> {
     // Cases:
     //
     //  1. Identifier, toml: table, ident present       -> traverse
@@ -13,72 +13,54 @@ pub fn resolve<'doc>(toml: &'doc mut Value, tokens: &Token) -> Result<&'doc mut
     //  3. Identifier, toml: array                      -> error
     //  4. Index, toml: table                           -> error
     //  5. Index, toml: array, idx present              -> traverse
-    //  6. Index, toml: array, idx not present
-    //      6.1 -> next token is ident                  -> push Table
-    //      6.2 -> next token is index                  -> push Array
-    //      then traverse
+    //  6. Index, toml: array, idx not present          -> error
+    //  7. Identifier or Index, toml: other value       -> error
 
     match *tokens {
-        Token::Identifier { ref ident, .. } => match toml {
-            Value::Table(ref mut t) => {
-                if t.contains_key(ident) {
-                    match tokens.next() {
-                        Some(next) => resolve(t.get_mut(ident).unwrap(), next),
-                        None => t.get_mut(ident).ok_or_else(|| unreachable!()),
-                    }
-                } else {
-                    match tokens.next() {
-                        Some(next) => {
-                            let subdoc = t.entry(ident.clone()).or_insert(Value::Table(Map::new()));
-                            resolve(subdoc, next)
-                        }
-                        None => Ok(t.entry(ident.clone()).or_insert(Value::Table(Map::new()))),
-                    }
+        Token::Identifier { ref ident, .. } => match toml.kind() {
+            Kind::Table => {
+                if toml.get_key(ident).is_none() {
+                    toml.insert_key(ident.clone(), V::empty_table());
+                }
+
+                // safe because the key is present now
+                let subdoc = toml.get_key_mut(ident).unwrap();
+                match tokens.next() {
+                    Some(next) => resolve(subdoc, next),
+                    None => Ok(subdoc),
                 }
             }
-            Value::Array(_) => Err(Error::NoIdentifierInArray(ident.clone())),
-            _ => unimplemented!(),
+            Kind::Array => Err(Error::NoIdentifierInArray(ident.clone())),
+            Kind::Other => Err(Error::QueryingValueAsTable(ident.clone())),
         },
-        Token::Index { idx, .. } => {
-            match toml {
-                Value::Table(_) => Err(Error::NoIndexInTable(idx)),
-                Value::Array(ref mut ary) => {
-                    if ary.len() > idx {
-                        match tokens.next() {
-                            Some(next) => resolve(ary.get_mut(idx).unwrap(), next),
-                            None => ary.get_mut(idx).ok_or_else(|| unreachable!()),
-                        }
-                    } else if let Some(next) = tokens.next() {
-                        match next {
-                            Token::Identifier { .. } => {
-                                ary.push(Value::Table(Map::new()));
-                            }
-                            Token::Index { .. } => {
-                                ary.push(Value::Array(vec![]));
-                            }
-                        }
-                        //resolve(toml, next)
-                        panic!("Cannot do this")
-                    } else {
-                        unimplemented!()
-                    }
+        Token::Index { idx, .. } => match toml.kind() {
+            Kind::Table => Err(Error::NoIndexInTable(idx)),
+            Kind::Array => {
+                let len = toml.len();
+                match toml.get_index_mut(idx) {
+                    Some(subdoc) => match tokens.next() {
+                        Some(next) => resolve(subdoc, next),
+                        None => Ok(subdoc),
+                    },
+                    None => Err(Error::IndexOutOfBounds(idx, len)),
                 }
-                _ => unimplemented!(),
             }
-        }
+            Kind::Other => Err(Error::QueryingValueAsArray(idx)),
+        },
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::resolve;
+    use crate::error::Error;
     use crate::tokenizer::*;
     use toml::from_str as toml_from_str;
     use toml::Value;
 
     macro_rules! do_resolve {
         ( $toml:ident => $query:expr ) => {
-            resolve(
+            resolve::<Value>(
                 &mut $toml,
                 &tokenize_with_seperator(&String::from($query), '.').unwrap(),
             )
@@ -377,11 +359,11 @@ mod test {
         match result {
             Value::Table(ref tab) => {
                 match tab.get("color") {
-                    Some(&Value::String(ref s)) => assert_eq!("red", s),
+                    Some(Value::String(s)) => assert_eq!("red", s),
                     _ => unreachable!(),
                 }
                 match tab.get("shape") {
-                    Some(&Value::String(ref s)) => assert_eq!("round", s),
+                    Some(Value::String(s)) => assert_eq!("round", s),
                     _ => unreachable!(),
                 }
             }
@@ -506,4 +488,15 @@ mod test {
         //    _                        => panic!("What just happened?"),
         //}
     }
+
+    #[test]
+    fn test_resolve_through_non_structure_value() {
+        let mut toml = toml_from_str("example = 1\narray = [1]").unwrap();
+
+        let result = do_resolve!(toml => "example.foo");
+        assert!(is_match!(result, Err(Error::QueryingValueAsTable(_))));
+
+        let result = do_resolve!(toml => "array.[1].foo");
+        assert!(is_match!(result, Err(Error::IndexOutOfBounds(1, 1))));
+    }
 }
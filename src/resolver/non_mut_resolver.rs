@@ -1,9 +1,7 @@
 /// The query resolver that operates on the AST and the TOML object
-use std::ops::Index;
-
 use crate::error::{Error, Result};
+use crate::queryable::{Kind, QueryableValue};
 use crate::tokenizer::Token;
-use toml::Value;
 
 /// Resolves the path in the passed document recursively
 ///
@@ -11,14 +9,14 @@ use toml::Value;
 ///
 /// If error_if_not_found is set to true, this function does not return Ok(None) in any case.
 ///
-pub fn resolve<'doc>(
-    toml: &'doc Value,
+pub fn resolve<'doc, V: QueryableValue>(
+    toml: &'doc V,
     tokens: &Token,
     error_if_not_found: bool,
-) -> Result<Option<&'doc Value>> {
-    match toml {
-        Value::Table(ref t) => match tokens {
-            Token::Identifier { ref ident, .. } => match t.get(ident) {
+) -> Result<Option<&'doc V>> {
+    match toml.kind() {
+        Kind::Table => match tokens {
+            Token::Identifier { ref ident, .. } => match toml.get_key(ident) {
                 None => {
                     if error_if_not_found {
                         Err(Error::IdentifierNotFoundInDocument(ident.to_owned()))
@@ -35,21 +33,18 @@ pub fn resolve<'doc>(
             Token::Index { idx, .. } => Err(Error::NoIndexInTable(*idx)),
         },
 
-        Value::Array(ref ary) => match tokens {
-            Token::Index { idx, .. } => match tokens.next() {
-                Some(next) => resolve(ary.get(*idx).unwrap(), next, error_if_not_found),
-                None => {
-                    if ary.get(*idx).is_none() {
-                        Err(Error::IndexOutOfBounds(*idx, ary.len()))
-                    } else {
-                        Ok(Some(ary.index(*idx)))
-                    }
-                }
+        Kind::Array => match tokens {
+            Token::Index { idx, .. } => match toml.get_index(*idx) {
+                None => Err(Error::IndexOutOfBounds(*idx, toml.len())),
+                Some(sub_document) => match tokens.next() {
+                    Some(next) => resolve(sub_document, next, error_if_not_found),
+                    None => Ok(Some(sub_document)),
+                },
             },
             Token::Identifier { ref ident, .. } => Err(Error::NoIdentifierInArray(ident.clone())),
         },
 
-        _ => match tokens {
+        Kind::Other => match tokens {
             Token::Identifier { ref ident, .. } => Err(Error::QueryingValueAsTable(ident.clone())),
 
             Token::Index { idx, .. } => Err(Error::QueryingValueAsArray(*idx)),
@@ -67,7 +62,7 @@ mod test {
 
     macro_rules! do_resolve {
         ( $toml:ident => $query:expr ) => {
-            resolve(
+            resolve::<Value>(
                 &$toml,
                 &tokenize_with_seperator(&String::from($query), '.').unwrap(),
                 true,
@@ -83,7 +78,10 @@ mod test {
         assert!(result.is_err());
         let result = result.unwrap_err();
 
-        assert!(is_match!(result, Error::IdentifierNotFoundInDocument { .. }));
+        assert!(is_match!(
+            result,
+            Error::IdentifierNotFoundInDocument { .. }
+        ));
     }
 
     #[test]
@@ -412,11 +410,11 @@ mod test {
         match result {
             Value::Table(ref tab) => {
                 match tab.get("color") {
-                    Some(&Value::String(ref s)) => assert_eq!("red", s),
+                    Some(Value::String(s)) => assert_eq!("red", s),
                     _ => unreachable!(),
                 }
                 match tab.get("shape") {
-                    Some(&Value::String(ref s)) => assert_eq!("round", s),
+                    Some(Value::String(s)) => assert_eq!("round", s),
                     _ => unreachable!(),
                 }
             }
@@ -487,7 +485,10 @@ mod test {
         assert!(result.is_err());
         let result = result.unwrap_err();
 
-        assert!(is_match!(result, Error::IdentifierNotFoundInDocument { .. }));
+        assert!(is_match!(
+            result,
+            Error::IdentifierNotFoundInDocument { .. }
+        ));
     }
 
     #[test]
@@ -0,0 +1,333 @@
+//! The Toml Restructure extensions
+
+use toml::Value;
+
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::read::TomlValueReadExt;
+use crate::tokenizer::tokenize_with_seperator;
+use crate::tokenizer::Token;
+use crate::util::{insert_path, remove_path, token_path};
+
+pub trait TomlValueRestructureExt {
+    /// Extension function for moving a value in the current toml::Value document
+    /// using a custom seperator.
+    ///
+    /// # Semantics
+    ///
+    /// The value at `from` is removed from the document, even if it is a non-empty table or
+    /// array, and then inserted at `to`. Inserting works like `TomlValueInsertExt::insert`, so
+    /// intermediate tables are created at the target.
+    ///
+    /// The query `to` is resolved _after_ the value was removed. So moving `"a.[0]"` to
+    /// `"a.[2]"` results in the element being at index `2` after the move.
+    ///
+    /// If the value cannot be inserted at `to`, it is put back to `from`.
+    ///
+    /// # Return value
+    ///
+    /// * If the move operation worked correctly, `Ok(None)` is returned.
+    /// * If the move operation replaced an existing value `Ok(Some(old_value))` is returned
+    /// * On failure, `Err(e)` is returned:
+    ///     * If there is no value at `from`: error
+    ///     * If `to` is inside of `from`: error
+    ///     * If inserting at `to` fails: error
+    ///
+    fn move_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>>;
+
+    /// Extension function for moving a value in the current toml::Value document
+    ///
+    /// See documentation of `TomlValueRestructureExt::move_value_with_seperator`
+    fn move_value(&mut self, from: &str, to: &str) -> Result<Option<Value>> {
+        self.move_value_with_seperator(from, to, '.')
+    }
+
+    /// Extension function for copying a value in the current toml::Value document
+    /// using a custom seperator.
+    ///
+    /// # Semantics
+    ///
+    /// The value at `from` is cloned and inserted at `to`. Inserting works like
+    /// `TomlValueInsertExt::insert`, so intermediate tables are created at the target.
+    ///
+    /// # Return value
+    ///
+    /// * If the copy operation worked correctly, `Ok(None)` is returned.
+    /// * If the copy operation replaced an existing value `Ok(Some(old_value))` is returned
+    /// * On failure, `Err(e)` is returned:
+    ///     * If there is no value at `from`: error
+    ///     * If inserting at `to` fails: error
+    ///
+    fn copy_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>>;
+
+    /// Extension function for copying a value in the current toml::Value document
+    ///
+    /// See documentation of `TomlValueRestructureExt::copy_value_with_seperator`
+    fn copy_value(&mut self, from: &str, to: &str) -> Result<Option<Value>> {
+        self.copy_value_with_seperator(from, to, '.')
+    }
+
+    /// Extension function for renaming a key of a table in the current toml::Value document
+    /// using a custom seperator.
+    ///
+    /// # Semantics
+    ///
+    /// The key at `query` is renamed to `new_name` in the same table. If the table keeps the
+    /// order of its keys, the renamed key keeps its position.
+    ///
+    /// # Return value
+    ///
+    /// * If the rename operation worked correctly, `Ok(None)` is returned.
+    /// * If there already was a key `new_name`, its value is replaced and `Ok(Some(old_value))`
+    ///   is returned
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query does not end with a key: `Error::CannotRenameIndex`
+    ///     * If there is no value at `query`: error
+    ///
+    fn rename_key_with_seperator(
+        &mut self,
+        query: &str,
+        new_name: &str,
+        sep: char,
+    ) -> Result<Option<Value>>;
+
+    /// Extension function for renaming a key of a table in the current toml::Value document
+    ///
+    /// See documentation of `TomlValueRestructureExt::rename_key_with_seperator`
+    fn rename_key(&mut self, query: &str, new_name: &str) -> Result<Option<Value>> {
+        self.rename_key_with_seperator(query, new_name, '.')
+    }
+}
+
+impl TomlValueRestructureExt for Value {
+    fn move_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        let from_path = token_path(&tokenize_with_seperator(from, sep)?);
+        let to_path = token_path(&tokenize_with_seperator(to, sep)?);
+
+        if to_path.starts_with(&from_path) {
+            return Err(Error::CannotMoveIntoItself(from.to_owned(), to.to_owned()));
+        }
+
+        let value =
+            remove_path(self, &from_path).ok_or_else(|| Error::NotAvailable(from.to_owned()))?;
+
+        match self.insert_with_seperator(to, sep, value.clone()) {
+            Ok(old) => Ok(old),
+            Err(e) => {
+                let _ = insert_path(self, &from_path, value); // restore the source
+                Err(e)
+            }
+        }
+    }
+
+    fn copy_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        let value = self
+            .read_with_seperator(from, sep)?
+            .cloned()
+            .ok_or_else(|| Error::NotAvailable(from.to_owned()))?;
+
+        self.insert_with_seperator(to, sep, value)
+    }
+
+    fn rename_key_with_seperator(
+        &mut self,
+        query: &str,
+        new_name: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        use crate::resolver::mut_resolver::resolve;
+
+        let mut tokens = tokenize_with_seperator(query, sep)?;
+        let (val, last) = match tokens.pop_last() {
+            None => (self, Box::new(tokens)),
+            // safe because of resolve() guarantees
+            Some(last) => (resolve(self, &tokens, true)?.unwrap(), last),
+        };
+
+        let ident = match *last {
+            Token::Identifier { ident, .. } => ident,
+            Token::Index { idx, .. } => return Err(Error::CannotRenameIndex(idx)),
+        };
+
+        match val {
+            Value::Table(ref mut t) => {
+                if !t.contains_key(&ident) {
+                    return Err(Error::NotAvailable(query.to_owned()));
+                }
+                if ident == new_name {
+                    return Ok(None);
+                }
+
+                let old = t.remove(new_name);
+
+                // Rebuild the table, so the key keeps its position in ordered tables
+                *t = std::mem::take(t)
+                    .into_iter()
+                    .map(|(k, v)| {
+                        if k == ident {
+                            (new_name.to_owned(), v)
+                        } else {
+                            (k, v)
+                        }
+                    })
+                    .collect();
+
+                Ok(old)
+            }
+            Value::Array(_) => Err(Error::NoIdentifierInArray(ident)),
+            _ => Err(Error::QueryingValueAsTable(ident)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_move_value_creates_target_tables() {
+        let mut toml: Value = toml_from_str("[server]\naddr = \"0.0.0.0\"\nport = 80").unwrap();
+
+        let res = toml
+            .move_value("server.addr", "network.listen.addr")
+            .unwrap();
+
+        assert!(res.is_none());
+        let expected: Value =
+            toml_from_str("[server]\nport = 80\n[network.listen]\naddr = \"0.0.0.0\"").unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_move_non_empty_table() {
+        let mut toml: Value = toml_from_str("[a.b]\nc = 1\n[x]\ny = 2").unwrap();
+
+        let res = toml.move_value("a.b", "x.y").unwrap();
+
+        assert_eq!(res, Some(Value::Integer(2)));
+        assert_eq!(toml, toml_from_str::<Value>("[a]\n[x.y]\nc = 1").unwrap());
+    }
+
+    #[test]
+    fn test_move_value_within_array() {
+        let mut toml: Value = toml_from_str("a = [1, 2, 3]").unwrap();
+
+        toml.move_value("a.[0]", "a.[2]").unwrap();
+
+        assert_eq!(toml, toml_from_str::<Value>("a = [2, 3, 1]").unwrap());
+    }
+
+    #[test]
+    fn test_move_nonexistent_value() {
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+
+        let res = toml.move_value("b", "c");
+
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+        assert_eq!(toml, toml_from_str::<Value>("a = 1").unwrap());
+    }
+
+    #[test]
+    fn test_move_value_into_itself() {
+        let mut toml: Value = toml_from_str("[a]\nb = 1").unwrap();
+
+        let res = toml.move_value("a", "a.c");
+
+        assert!(is_match!(res, Err(Error::CannotMoveIntoItself(_, _))));
+        assert_eq!(toml, toml_from_str::<Value>("[a]\nb = 1").unwrap());
+    }
+
+    #[test]
+    fn test_failed_move_restores_source() {
+        let mut toml: Value = toml_from_str("a = [1, 2]\nb = 3").unwrap();
+
+        let res = toml.move_value("a.[0]", "b.c");
+
+        assert!(res.is_err());
+        assert_eq!(toml, toml_from_str::<Value>("a = [1, 2]\nb = 3").unwrap());
+    }
+
+    #[test]
+    fn test_copy_value() {
+        let mut toml: Value = toml_from_str("[a]\nb = [1, 2]").unwrap();
+
+        let res = toml.copy_value("a", "a.c").unwrap();
+
+        assert!(res.is_none());
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[a]\nb = [1, 2]\n[a.c]\nb = [1, 2]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_copy_nonexistent_value() {
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+
+        let res = toml.copy_value("b", "c");
+
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+    }
+
+    #[test]
+    fn test_rename_key() {
+        let mut toml: Value = toml_from_str("[server]\naddr = \"0.0.0.0\"\nport = 80").unwrap();
+
+        let res = toml.rename_key("server.addr", "host").unwrap();
+
+        assert!(res.is_none());
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[server]\nhost = \"0.0.0.0\"\nport = 80").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rename_top_level_key_replaces_existing() {
+        let mut toml: Value = toml_from_str("a = 1\nb = 2").unwrap();
+
+        let res = toml.rename_key("a", "b").unwrap();
+
+        assert_eq!(res, Some(Value::Integer(2)));
+        assert_eq!(toml, toml_from_str::<Value>("b = 1").unwrap());
+    }
+
+    #[test]
+    fn test_rename_array_element() {
+        let mut toml: Value = toml_from_str("a = [1]").unwrap();
+
+        let res = toml.rename_key("a.[0]", "b");
+
+        assert!(is_match!(res, Err(Error::CannotRenameIndex(0))));
+    }
+
+    #[test]
+    fn test_rename_nonexistent_key() {
+        let mut toml: Value = toml_from_str("[a]").unwrap();
+
+        let res = toml.rename_key("a.b", "c");
+
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+    }
+}
@@ -0,0 +1,306 @@
+//! Extensions for documents with a root other than `toml::Value`
+//!
+//! The extension traits are implemented for `toml::value::Table` (a parsed document), for
+//! `Vec<Value>` and for `Option<Value>`, where `None` is a document which does not exist (yet).
+//! Reading from `None` finds nothing, modifying it starts with an empty table.
+
+use toml::value::{Table, Value};
+
+use crate::delete::TomlValueDeleteExt;
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::read::TomlValueReadExt;
+use crate::resolver::{mut_resolver, non_mut_resolver};
+use crate::set::{SetOptions, TomlValueSetExt};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::tokenizer::Token;
+use crate::value::TomlValueExt;
+
+/// A document root which is not a `toml::Value`
+trait Root {
+    /// Resolve the query in `tokens`, like `non_mut_resolver::resolve()` without erroring if
+    /// it is not found
+    fn resolve(&self, tokens: &Token) -> Result<Option<&Value>>;
+
+    /// Resolve the query in `tokens` mutably
+    fn resolve_mut(&mut self, tokens: &Token) -> Result<Option<&mut Value>>;
+
+    /// Run `f` on the root as a `toml::Value`
+    fn modify<R, F>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Value) -> Result<R>;
+}
+
+impl Root for Table {
+    fn resolve(&self, tokens: &Token) -> Result<Option<&Value>> {
+        match tokens {
+            Token::Identifier { ref ident, .. } => match (self.get(ident), tokens.next()) {
+                (Some(sub), Some(next)) => non_mut_resolver::resolve(sub, next, false),
+                (sub, _) => Ok(sub),
+            },
+            Token::Index { idx, .. } => Err(Error::NoIndexInTable(*idx)),
+        }
+    }
+
+    fn resolve_mut(&mut self, tokens: &Token) -> Result<Option<&mut Value>> {
+        match tokens {
+            Token::Identifier { ref ident, .. } => match (self.get_mut(ident), tokens.next()) {
+                (Some(sub), Some(next)) => mut_resolver::resolve(sub, next, false),
+                (sub, _) => Ok(sub),
+            },
+            Token::Index { idx, .. } => Err(Error::NoIndexInTable(*idx)),
+        }
+    }
+
+    fn modify<R, F>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Value) -> Result<R>,
+    {
+        let mut value = Value::Table(std::mem::take(self));
+        let result = f(&mut value);
+
+        // The root itself is never replaced by a query, only its contents
+        if let Value::Table(table) = value {
+            *self = table;
+        }
+        result
+    }
+}
+
+impl Root for Vec<Value> {
+    fn resolve(&self, tokens: &Token) -> Result<Option<&Value>> {
+        match tokens {
+            Token::Index { idx, .. } => match (self.get(*idx), tokens.next()) {
+                (None, _) => Err(Error::IndexOutOfBounds(*idx, self.len())),
+                (Some(sub), Some(next)) => non_mut_resolver::resolve(sub, next, false),
+                (sub, None) => Ok(sub),
+            },
+            Token::Identifier { ref ident, .. } => Err(Error::NoIdentifierInArray(ident.clone())),
+        }
+    }
+
+    fn resolve_mut(&mut self, tokens: &Token) -> Result<Option<&mut Value>> {
+        match tokens {
+            Token::Index { idx, .. } => {
+                let len = self.len();
+                match (self.get_mut(*idx), tokens.next()) {
+                    (None, _) => Err(Error::IndexOutOfBounds(*idx, len)),
+                    (Some(sub), Some(next)) => mut_resolver::resolve(sub, next, false),
+                    (sub, None) => Ok(sub),
+                }
+            }
+            Token::Identifier { ref ident, .. } => Err(Error::NoIdentifierInArray(ident.clone())),
+        }
+    }
+
+    fn modify<R, F>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Value) -> Result<R>,
+    {
+        let mut value = Value::Array(std::mem::take(self));
+        let result = f(&mut value);
+
+        // The root itself is never replaced by a query, only its contents
+        if let Value::Array(array) = value {
+            *self = array;
+        }
+        result
+    }
+}
+
+impl Root for Option<Value> {
+    fn resolve(&self, tokens: &Token) -> Result<Option<&Value>> {
+        match self {
+            Some(ref value) => non_mut_resolver::resolve(value, tokens, false),
+            None => Ok(None),
+        }
+    }
+
+    fn resolve_mut(&mut self, tokens: &Token) -> Result<Option<&mut Value>> {
+        match self {
+            Some(ref mut value) => mut_resolver::resolve(value, tokens, false),
+            None => Ok(None),
+        }
+    }
+
+    fn modify<R, F>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Value) -> Result<R>,
+    {
+        let existed = self.is_some();
+        let mut value = self.take().unwrap_or_else(|| Value::Table(Table::new()));
+        let result = f(&mut value);
+
+        // A failed modification does not create the document
+        if existed || result.is_ok() {
+            *self = Some(value);
+        }
+        result
+    }
+}
+
+/// Implement the extension traits for a type which implements `Root`
+macro_rules! impl_root_ext {
+    ($root:ty) => {
+        impl<'doc> TomlValueReadExt<'doc> for $root {
+            fn read_with_seperator(
+                &'doc self,
+                query: &str,
+                sep: char,
+            ) -> Result<Option<&'doc Value>> {
+                let tokens = tokenize_with_seperator(query, sep)?;
+                Root::resolve(self, &tokens)
+            }
+
+            fn read_mut_with_seperator(
+                &'doc mut self,
+                query: &str,
+                sep: char,
+            ) -> Result<Option<&'doc mut Value>> {
+                let tokens = tokenize_with_seperator(query, sep)?;
+                Root::resolve_mut(self, &tokens)
+            }
+        }
+
+        impl TomlValueSetExt for $root {
+            fn set_with_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+                value: Value,
+            ) -> Result<Option<Value>> {
+                self.modify(|root| TomlValueSetExt::set_with_seperator(root, query, sep, value))
+            }
+
+            fn set_all_with_seperator(
+                &mut self,
+                pattern: &str,
+                sep: char,
+                value: Value,
+            ) -> Result<Vec<(String, Value)>> {
+                self.modify(|root| root.set_all_with_seperator(pattern, sep, value))
+            }
+
+            fn set_with_options_and_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+                value: Value,
+                options: SetOptions,
+            ) -> Result<Option<Value>> {
+                self.modify(|root| root.set_with_options_and_seperator(query, sep, value, options))
+            }
+        }
+
+        impl TomlValueInsertExt for $root {
+            fn insert_with_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+                value: Value,
+            ) -> Result<Option<Value>> {
+                self.modify(|root| {
+                    TomlValueInsertExt::insert_with_seperator(root, query, sep, value)
+                })
+            }
+        }
+
+        impl TomlValueDeleteExt for $root {
+            fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+                self.modify(|root| TomlValueDeleteExt::delete_with_seperator(root, query, sep))
+            }
+
+            fn delete_recursive_with_seperator(
+                &mut self,
+                query: &str,
+                sep: char,
+                prune: bool,
+            ) -> Result<Option<Value>> {
+                self.modify(|root| root.delete_recursive_with_seperator(query, sep, prune))
+            }
+
+            fn delete_all_with_seperator(
+                &mut self,
+                pattern: &str,
+                sep: char,
+            ) -> Result<Vec<(String, Value)>> {
+                self.modify(|root| root.delete_all_with_seperator(pattern, sep))
+            }
+        }
+
+        impl<'doc> TomlValueExt<'doc> for $root {}
+    };
+}
+
+impl_root_ext!(Table);
+impl_root_ext!(Vec<Value>);
+impl_root_ext!(Option<Value>);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_table() {
+        let mut table: Table = toml_from_str("[a]\nb = [1, 2]").unwrap();
+
+        assert_eq!(
+            TomlValueReadExt::read(&table, "a.b.[1]").unwrap(),
+            Some(&Value::Integer(2))
+        );
+        assert_eq!(TomlValueReadExt::read(&table, "c").unwrap(), None);
+        assert!(is_match!(
+            TomlValueReadExt::read(&table, "[0]"),
+            Err(Error::NoIndexInTable(0))
+        ));
+
+        TomlValueSetExt::set(&mut table, "a.b.[0]", Value::Integer(0)).unwrap();
+        TomlValueInsertExt::insert(&mut table, "c.d", Value::Boolean(true)).unwrap();
+        TomlValueDeleteExt::delete(&mut table, "a.b.[1]").unwrap();
+        *TomlValueReadExt::read_mut(&mut table, "c.d")
+            .unwrap()
+            .unwrap() = Value::Integer(3);
+
+        let expected: Table = toml_from_str("[a]\nb = [0]\n[c]\nd = 3").unwrap();
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_array() {
+        let mut array = vec![Value::Integer(1), Value::Array(vec![])];
+
+        assert_eq!(
+            TomlValueReadExt::read(&array, "[0]").unwrap(),
+            Some(&Value::Integer(1))
+        );
+        assert!(is_match!(
+            TomlValueReadExt::read(&array, "[2]"),
+            Err(Error::IndexOutOfBounds(2, 2))
+        ));
+        assert!(is_match!(
+            TomlValueReadExt::read(&array, "a"),
+            Err(Error::NoIdentifierInArray(_))
+        ));
+
+        TomlValueInsertExt::insert(&mut array, "[1].[0]", Value::Integer(2)).unwrap();
+        TomlValueDeleteExt::delete(&mut array, "[0]").unwrap();
+
+        assert_eq!(array, vec![Value::Array(vec![Value::Integer(2)])]);
+    }
+
+    #[test]
+    fn test_option() {
+        let mut doc: Option<Value> = None;
+
+        assert_eq!(TomlValueReadExt::read(&doc, "a").unwrap(), None);
+        assert!(TomlValueSetExt::set(&mut doc, "a.b", Value::Integer(1)).is_err());
+        assert_eq!(doc, None);
+
+        TomlValueInsertExt::insert(&mut doc, "a.b", Value::Integer(1)).unwrap();
+        assert_eq!(
+            TomlValueReadExt::read(&doc, "a.b").unwrap(),
+            Some(&Value::Integer(1))
+        );
+    }
+}
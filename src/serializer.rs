@@ -0,0 +1,547 @@
+//! A serde Serializer writing into an existing `toml::Value`
+//!
+//! `toml::Value::try_from()` always creates a new document. The `ValueSerializer` writes into a
+//! value of an existing document instead. When merging, structs and maps are written into an
+//! existing table field by field: keys the serialized value does not mention, including fields
+//! which are `None` or skipped with `#[serde(skip_serializing_if = "...")]`, are kept.
+//!
+//! Everything but structs and maps is serialized like with `toml::Value::try_from()`, arrays are
+//! replaced as a whole.
+
+use serde::ser::{self, Impossible, Serialize};
+use toml::map::Map;
+use toml::ser::Error;
+use toml::value::Datetime;
+use toml::Value;
+
+/// The name of the struct which `toml::value::Datetime` serializes as
+const DATETIME_NAME: &str = "$__toml_private_Datetime";
+
+/// Serializer writing into a borrowed `toml::Value`
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serde;
+/// # extern crate toml;
+/// # extern crate toml_query;
+/// use serde::Serialize;
+/// use toml_query::serializer::ValueSerializer;
+///
+/// let mut value : toml::Value = toml::from_str("a = 1\nb = 2").unwrap();
+///
+/// let mut update = std::collections::BTreeMap::new();
+/// update.insert("a", 3);
+/// update.serialize(ValueSerializer::merging(&mut value)).unwrap();
+///
+/// assert_eq!(value, toml::from_str("a = 3\nb = 2").unwrap());
+/// ```
+#[derive(Debug)]
+pub struct ValueSerializer<'doc> {
+    target: &'doc mut Value,
+    merge: bool,
+}
+
+impl<'doc> ValueSerializer<'doc> {
+    /// Serializer replacing `target` with the serialized value
+    pub fn new(target: &'doc mut Value) -> Self {
+        ValueSerializer {
+            target,
+            merge: false,
+        }
+    }
+
+    /// Serializer merging the serialized value into `target`
+    pub fn merging(target: &'doc mut Value) -> Self {
+        ValueSerializer {
+            target,
+            merge: true,
+        }
+    }
+
+    fn replace<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        *self.target = Value::try_from(value)?;
+        Ok(())
+    }
+}
+
+macro_rules! serialize_as_value {
+    ($($fnname:ident: $type:ty),*) => {
+        $(
+            fn $fnname(self, value: $type) -> Result<(), Error> {
+                self.replace(&value)
+            }
+        )*
+    };
+}
+
+impl<'doc> ser::Serializer for ValueSerializer<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray<'doc>;
+    type SerializeTuple = SerializeArray<'doc>;
+    type SerializeTupleStruct = SerializeArray<'doc>;
+    type SerializeTupleVariant = SerializeArray<'doc>;
+    type SerializeMap = SerializeTable<'doc>;
+    type SerializeStruct = SerializeTable<'doc>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    serialize_as_value! {
+        serialize_bool: bool,
+        serialize_i8: i8, serialize_i16: i16, serialize_i32: i32, serialize_i64: i64,
+        serialize_u8: u8, serialize_u16: u16, serialize_u32: u32, serialize_u64: u64,
+        serialize_f32: f32, serialize_f64: f64,
+        serialize_char: char, serialize_str: &str, serialize_bytes: &[u8]
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(Error::UnsupportedType)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(Error::UnsupportedType)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.replace(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(Error::UnsupportedType)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(Error::UnsupportedNone)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray<'doc>, Error> {
+        Ok(SerializeArray {
+            target: self.target,
+            array: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray<'doc>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray<'doc>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray<'doc>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeTable<'doc>, Error> {
+        let table = match self.target {
+            Value::Table(ref table) if self.merge => table.clone(),
+            _ => Map::new(),
+        };
+
+        Ok(SerializeTable {
+            target: self.target,
+            table,
+            merge: self.merge,
+            datetime: false,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SerializeTable<'doc>, Error> {
+        if name == DATETIME_NAME {
+            Ok(SerializeTable {
+                target: self.target,
+                table: Map::new(),
+                merge: false,
+                datetime: true,
+                key: None,
+            })
+        } else {
+            self.serialize_map(Some(len))
+        }
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<(), Error>, Error> {
+        Err(Error::UnsupportedType)
+    }
+}
+
+/// Serializer for arrays, which are replaced as a whole
+#[derive(Debug)]
+pub struct SerializeArray<'doc> {
+    target: &'doc mut Value,
+    array: Vec<Value>,
+}
+
+impl<'doc> SerializeArray<'doc> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.array.push(Value::try_from(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        *self.target = Value::Array(self.array);
+        Ok(())
+    }
+}
+
+impl<'doc> ser::SerializeSeq for SerializeArray<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'doc> ser::SerializeTuple for SerializeArray<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'doc> ser::SerializeTupleStruct for SerializeArray<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'doc> ser::SerializeTupleVariant for SerializeArray<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Serializer for structs and maps, which writes into the table key by key
+///
+/// The keys are written into a copy of the table, which replaces the target when the table is
+/// complete. So if serializing fails, the target is not modified.
+#[derive(Debug)]
+pub struct SerializeTable<'doc> {
+    target: &'doc mut Value,
+    table: Map<String, Value>,
+    merge: bool,
+    datetime: bool,
+    key: Option<String>,
+}
+
+impl<'doc> SerializeTable<'doc> {
+    fn write<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        if self.datetime {
+            return match Value::try_from(value)? {
+                Value::String(s) => {
+                    let datetime = s.parse::<Datetime>().map_err(|_| Error::DateInvalid)?;
+                    *self.target = Value::Datetime(datetime);
+                    Ok(())
+                }
+                _ => Err(Error::DateInvalid),
+            };
+        }
+
+        let table = &mut self.table;
+        let existed = table.contains_key(&key);
+        let target = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Map::new()));
+        let serializer = ValueSerializer {
+            target,
+            merge: self.merge && existed,
+        };
+
+        match value.serialize(serializer) {
+            Ok(()) => Ok(()),
+
+            // A missing value is a missing field, which keeps the one in the document
+            Err(Error::UnsupportedNone) if existed && self.merge => Ok(()),
+
+            Err(e) => {
+                if !existed {
+                    table.remove(&key);
+                }
+
+                match e {
+                    Error::UnsupportedNone => Ok(()),
+                    e => Err(e),
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if !self.datetime {
+            *self.target = Value::Table(self.table);
+        }
+        Ok(())
+    }
+}
+
+impl<'doc> ser::SerializeMap for SerializeTable<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        match Value::try_from(key)? {
+            Value::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::KeyNotString),
+        }
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        match self.key.take() {
+            Some(key) => self.write(key, value),
+            None => Err(ser::Error::custom("key is missing")),
+        }
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'doc> ser::SerializeStruct for SerializeTable<'doc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.write(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[derive(Serialize)]
+    struct Server {
+        name: String,
+        port: Option<u16>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        tls: Tls,
+    }
+
+    #[derive(Serialize)]
+    struct Tls {
+        enabled: bool,
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut doc: Value = toml_from_str(
+            "name = \"alpha\"\nport = 80\ntags = [\"a\"]\n[tls]\ncert = \"cert.pem\"",
+        )
+        .unwrap();
+        let server = Server {
+            name: String::from("beta"),
+            port: None,
+            tags: vec![],
+            tls: Tls { enabled: true },
+        };
+        server.serialize(ValueSerializer::new(&mut doc)).unwrap();
+
+        let expected: Value = toml_from_str("name = \"beta\"\n[tls]\nenabled = true").unwrap();
+        assert_eq!(doc, expected);
+        assert_eq!(doc, Value::try_from(&server).unwrap());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut doc: Value = toml_from_str(
+            r#"
+        name = "alpha"
+        port = 80
+        tags = ["a"]
+        other = 1
+        [tls]
+        enabled = false
+        cert = "cert.pem"
+        "#,
+        )
+        .unwrap();
+        let server = Server {
+            name: String::from("beta"),
+            port: None,
+            tags: vec![],
+            tls: Tls { enabled: true },
+        };
+        server
+            .serialize(ValueSerializer::merging(&mut doc))
+            .unwrap();
+
+        let expected: Value = toml_from_str(
+            r#"
+        name = "beta"
+        port = 80
+        tags = ["a"]
+        other = 1
+        [tls]
+        enabled = true
+        cert = "cert.pem"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(doc, expected);
+    }
+
+    #[test]
+    fn test_merge_into_non_table() {
+        let mut doc = Value::Integer(1);
+        let server = Server {
+            name: String::from("beta"),
+            port: Some(80),
+            tags: vec![String::from("a")],
+            tls: Tls { enabled: true },
+        };
+        server
+            .serialize(ValueSerializer::merging(&mut doc))
+            .unwrap();
+
+        assert_eq!(doc, Value::try_from(&server).unwrap());
+    }
+
+    #[test]
+    fn test_datetime() {
+        let mut doc = Value::Integer(1);
+        let datetime: Datetime = "1979-05-27T07:32:00Z".parse().unwrap();
+        datetime.serialize(ValueSerializer::new(&mut doc)).unwrap();
+
+        assert_eq!(doc, Value::Datetime(datetime));
+    }
+
+    #[test]
+    fn test_failure_keeps_document_readable() {
+        let mut doc: Value = toml_from_str("a = 1\n[b]\nc = 2").unwrap();
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("new", u64::MAX);
+
+        let result = map.serialize(ValueSerializer::merging(&mut doc));
+        assert!(result.is_err());
+        assert_eq!(doc, toml_from_str::<Value>("a = 1\n[b]\nc = 2").unwrap());
+    }
+
+    #[test]
+    fn test_failure_keeps_document() {
+        #[derive(Serialize)]
+        struct Invalid {
+            name: &'static str,
+            port: u64,
+        }
+        let invalid = Invalid {
+            name: "beta",
+            port: u64::MAX,
+        };
+
+        // The name is serialized before the port fails
+        let mut doc: Value = toml_from_str("name = \"alpha\"\nport = 80").unwrap();
+        assert!(invalid.serialize(ValueSerializer::new(&mut doc)).is_err());
+        assert_eq!(
+            doc,
+            toml_from_str::<Value>("name = \"alpha\"\nport = 80").unwrap()
+        );
+
+        assert!(invalid
+            .serialize(ValueSerializer::merging(&mut doc))
+            .is_err());
+        assert_eq!(
+            doc,
+            toml_from_str::<Value>("name = \"alpha\"\nport = 80").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_newtype_variant_is_unsupported() {
+        #[derive(Serialize)]
+        enum Listen {
+            Port(u16),
+        }
+
+        let mut doc: Value = toml_from_str("port = 80").unwrap();
+        let result = Listen::Port(5).serialize(ValueSerializer::new(&mut doc));
+
+        assert!(is_match!(result, Err(Error::UnsupportedType)));
+        assert!(Value::try_from(Listen::Port(5)).is_err());
+        assert_eq!(doc, toml_from_str::<Value>("port = 80").unwrap());
+    }
+}
@@ -1,12 +1,70 @@
-/// The Toml Set extensions
+//! The Toml Set extensions
 
 #[cfg(feature = "typed")]
 use serde::Serialize;
 use toml::Value;
 
+#[cfg(feature = "typed")]
+use crate::delete::TomlValueDeleteExt;
 use crate::error::{Error, Result};
+#[cfg(feature = "typed")]
+use crate::read::Partial;
 use crate::tokenizer::tokenize_with_seperator;
-use crate::tokenizer::Token;
+use crate::util::{get_path, get_path_mut, name_of_val, path_to_query, remove_path, token_path};
+
+/// Options for `TomlValueSetExt::set_with_options_and_seperator`
+///
+/// The default options make the set operation behave like `TomlValueSetExt::set`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetOptions {
+    /// Refuse to replace an existing value with a value of another type
+    pub preserve_type: bool,
+
+    /// If the type is preserved, allow replacing a float with an integer, which is converted
+    /// to a float if it can be represented exactly
+    pub int_to_float: bool,
+
+    /// Create missing tables along the query, like `TomlValueInsertExt::insert` does
+    pub create_parents: bool,
+}
+
+impl SetOptions {
+    /// Check `value` against the value it replaces in `document`, converting it if allowed
+    pub(crate) fn prepare(
+        &self,
+        document: &Value,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Value> {
+        if !self.preserve_type {
+            return Ok(value);
+        }
+
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        let old = match get_path(document, &path) {
+            Some(old) => old,
+            None => return Ok(value),
+        };
+
+        match (old, value) {
+            // Integers up to 2^53 can be represented exactly
+            (Value::Float(_), Value::Integer(i))
+                if self.int_to_float && i.unsigned_abs() <= 1 << 53 =>
+            {
+                Ok(Value::Float(i as f64))
+            }
+            (old, value) => {
+                if name_of_val(old) == name_of_val(&value) {
+                    Ok(value)
+                } else {
+                    let (old, new) = (name_of_val(old), name_of_val(&value));
+                    Err(Error::CannotChangeType(query.to_owned(), old, new))
+                }
+            }
+        }
+    }
+}
 
 pub trait TomlValueSetExt {
     /// Extension function for setting a value in the current toml::Value document
@@ -37,12 +95,135 @@ pub trait TomlValueSetExt {
         self.set_with_seperator(query, '.', value)
     }
 
+    /// Extension function for setting a value at every location matching a pattern in the
+    /// current toml::Value document using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// The pattern is a query which may contain the wildcards `*`, which matches any single key
+    /// or array index, and `**`, which matches any number of keys or array indexes (including
+    /// none). So `"plugins.*.enabled"` matches `enabled` in every table in `plugins`, and
+    /// `"**.password"` matches every key `password` anywhere in the document.
+    ///
+    /// Only existing values are replaced, the function _never_ creates values. If a matching
+    /// value is inside of another matching value, only the outer one is replaced.
+    ///
+    /// # Return value
+    ///
+    /// * If the set operation worked correctly, `Ok(replaced)` is returned, where `replaced`
+    ///   contains the query and the old value of every replaced value, in document order.
+    /// * On failure, `Err(e)` is returned and the document is not modified:
+    ///     * If the pattern cannot be parsed: error
+    ///     * If the key of a matching value cannot be expressed in a query: error
+    ///
+    fn set_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<(String, Value)>>;
+
+    /// Extension function for setting a value at every location matching a pattern in the
+    /// current toml::Value document
+    ///
+    /// See documentation of `TomlValueSetExt::set_all_with_seperator`
+    fn set_all(&mut self, pattern: &str, value: Value) -> Result<Vec<(String, Value)>> {
+        self.set_all_with_seperator(pattern, '.', value)
+    }
+
+    /// Extension function for setting a value in the current toml::Value document
+    /// using a custom seperator and options
+    ///
+    /// # Semantics
+    ///
+    /// Works like `TomlValueSetExt::set_with_seperator`, but with `options.preserve_type` an
+    /// existing value is only replaced by a value of the same type. If there is no value at the
+    /// query yet, the value is set regardless of its type.
+    ///
+    /// With `options.int_to_float`, an existing float can also be replaced by an integer, which
+    /// is converted to a float. Integers which cannot be represented exactly as a float are
+    /// rejected.
+    ///
+    /// With `options.create_parents`, missing tables along the query are created, so setting
+    /// `"a.b.c"` in an empty document creates the tables `"a"` and `"b"`. Unlike with
+    /// `TomlValueInsertExt::insert`, an existing array element is still replaced and not
+    /// shifted. If the set operation fails, the created tables are removed again.
+    ///
+    /// # Return value
+    ///
+    /// * If the set operation worked correctly, `Ok(None)` is returned.
+    /// * If the set operation replaced an existing value `Ok(Some(old_value))` is returned
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the type is preserved and the value has another type than the existing one:
+    ///       error
+    ///     * If setting fails, see `TomlValueSetExt::set_with_seperator`: error
+    ///
+    fn set_with_options_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>>;
+
+    /// Extension function for setting a value in the current toml::Value document with options
+    ///
+    /// See documentation of `TomlValueSetExt::set_with_options_and_seperator`
+    fn set_with_options(
+        &mut self,
+        query: &str,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>> {
+        self.set_with_options_and_seperator(query, '.', value, options)
+    }
+
+    /// Extension function for replacing a value in the current toml::Value document only with
+    /// a value of the same type
+    ///
+    /// See documentation of `TomlValueSetExt::set_with_options_and_seperator`
+    fn set_same_type(&mut self, query: &str, value: Value) -> Result<Option<Value>> {
+        let options = SetOptions {
+            preserve_type: true,
+            ..SetOptions::default()
+        };
+        self.set_with_options_and_seperator(query, '.', value, options)
+    }
+
+    /// Extension function for setting a value in the current toml::Value document, creating
+    /// missing tables along the query
+    ///
+    /// See documentation of `TomlValueSetExt::set_with_options_and_seperator`
+    fn set_or_create(&mut self, query: &str, value: Value) -> Result<Option<Value>> {
+        let options = SetOptions {
+            create_parents: true,
+            ..SetOptions::default()
+        };
+        self.set_with_options_and_seperator(query, '.', value, options)
+    }
+
     /// A convenience method for setting any arbitrary serializable value.
     #[cfg(feature = "typed")]
     fn set_serialized<S: Serialize>(&mut self, query: &str, value: S) -> Result<Option<Value>> {
         let value = Value::try_from(value).map_err(Error::TomlSerialize)?;
         self.set(query, value)
     }
+
+    /// Extension function for writing a `Partial` to its location in the current toml::Value
+    /// document
+    ///
+    /// The `Partial` is written with `Partial::set_in`, which by default sets the serialized
+    /// value at `P::LOCATION` like with `TomlValueSetExt::set_or_create`, creating missing
+    /// tables along the location. Types with field locations delete the values of missing
+    /// fields, which is why the document must support deleting as well.
+    #[cfg(feature = "typed")]
+    fn set_partial<'a, P>(&mut self, value: &P) -> Result<Option<Value>>
+    where
+        P: Partial<'a, Output = P>,
+        Self: TomlValueDeleteExt,
+    {
+        P::set_in(value, self)
+    }
 }
 
 impl TomlValueSetExt for Value {
@@ -52,36 +233,75 @@ impl TomlValueSetExt for Value {
         sep: char,
         value: Value,
     ) -> Result<Option<Value>> {
-        use crate::resolver::mut_resolver::resolve;
+        self.set_with_options_and_seperator(query, sep, value, SetOptions::default())
+    }
+
+    fn set_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<(String, Value)>> {
+        use crate::resolver::multi_resolver::resolve_outermost;
+
+        let tokens = tokenize_with_seperator(pattern, sep)?;
+        let paths = resolve_outermost(self, &tokens);
+        let queries = paths
+            .iter()
+            .map(|path| path_to_query(path, sep))
+            .collect::<Result<Vec<_>>>()?;
+
+        let replaced = paths
+            .iter()
+            .map(|path| {
+                let target = get_path_mut(self, path).unwrap(); // safe because resolved before
+                std::mem::replace(target, value.clone())
+            })
+            .collect::<Vec<_>>();
+
+        Ok(queries.into_iter().zip(replaced).collect())
+    }
+
+    fn set_with_options_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>> {
+        use crate::resolver::{mut_creating_resolver, mut_resolver};
+
+        let value = options.prepare(self, query, sep, value)?;
 
         let mut tokens = tokenize_with_seperator(query, sep)?;
-        let last = tokens.pop_last();
 
-        let val = resolve(self, &tokens, true)?.unwrap(); // safe because of resolve() guarantees
-        let last = last.unwrap_or_else(|| Box::new(tokens));
+        // The first table which does not exist yet and is created
+        let created = if options.create_parents {
+            let path = token_path(&tokens);
+            (1..path.len())
+                .find(|i| get_path(self, &path[..*i]).is_none())
+                .map(|i| path[..i].to_vec())
+        } else {
+            None
+        };
 
-        match *last {
-            Token::Identifier { ident, .. } => match val {
-                Value::Table(ref mut t) => Ok(t.insert(ident, value)),
-                Value::Array(_) => Err(Error::NoIdentifierInArray(ident)),
-                _ => Err(Error::QueryingValueAsTable(ident)),
-            },
+        let result = match tokens.pop_last() {
+            None => crate::queryable::set_last(self, tokens, value),
+            Some(last) if created.is_some() => mut_creating_resolver::resolve(self, &tokens)
+                .and_then(|val| crate::queryable::set_last(val, *last, value)),
+            Some(last) => {
+                // safe because of resolve() guarantees
+                let val = mut_resolver::resolve(self, &tokens, true)?.unwrap();
+                crate::queryable::set_last(val, *last, value)
+            }
+        };
 
-            Token::Index { idx, .. } => match val {
-                Value::Array(ref mut a) => {
-                    if a.len() > idx {
-                        let result = a.swap_remove(idx);
-                        a.insert(idx, value);
-                        Ok(Some(result))
-                    } else {
-                        a.push(value);
-                        Ok(None)
-                    }
-                }
-                Value::Table(_) => Err(Error::NoIndexInTable(idx)),
-                _ => Err(Error::QueryingValueAsArray(idx)),
-            },
+        if let (Err(_), Some(path)) = (&result, created) {
+            // Do not leave the created tables behind
+            remove_path(self, &path);
         }
+
+        result
     }
 }
 
@@ -222,6 +442,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_set_with_seperator_into_array_keeps_order() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        array = [ 0, 1, 2, 3 ]
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.set_with_seperator(&String::from("array.[1]"), '.', Value::Integer(4));
+
+        assert!(res.is_ok());
+        assert!(is_match!(res.unwrap(), Some(Value::Integer(1))));
+
+        let expected: Value = toml_from_str("array = [ 0, 4, 2, 3 ]").unwrap();
+        assert_eq!(toml, expected);
+    }
+
     #[test]
     fn test_set_with_seperator_into_table_index_nonexistent() {
         use std::ops::Index;
@@ -332,6 +570,28 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_set_with_seperator_top_level_value() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        value = 0
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.set_with_seperator(&String::from("value"), '.', Value::Integer(1));
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        assert!(is_match!(res, Some(Value::Integer(0))));
+
+        match toml {
+            Value::Table(ref t) => assert!(is_match!(t.get("value"), Some(Value::Integer(1)))),
+            _ => panic!("What just happenend?"),
+        }
+    }
+
     #[test]
     fn test_set_with_seperator_into_nonexistent_table() {
         let mut toml: Value = toml_from_str("").unwrap();
@@ -424,6 +684,176 @@ mod test {
         assert!(is_match!(res, Error::QueryingValueAsArray(_)));
     }
 
+    #[test]
+    fn test_set_all_matching_wildcard() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        [plugins.a]
+        enabled = true
+
+        [plugins.b]
+        name = "b"
+
+        [plugins.c]
+        enabled = true
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.set_all("plugins.*.enabled", Value::Boolean(false));
+
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert_eq!(
+            res,
+            vec![
+                (String::from("plugins.a.enabled"), Value::Boolean(true)),
+                (String::from("plugins.c.enabled"), Value::Boolean(true)),
+            ]
+        );
+
+        let expected: Value = toml_from_str(
+            r#"
+        [plugins.a]
+        enabled = false
+
+        [plugins.b]
+        name = "b"
+
+        [plugins.c]
+        enabled = false
+        "#,
+        )
+        .unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_set_all_never_creates_values() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        [table]
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.set_all("**.enabled", Value::Boolean(false));
+
+        assert!(res.is_ok());
+        assert!(res.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_all_with_unqueryable_key() {
+        let mut toml: Value = toml_from_str(
+            r#"
+        [table]
+        "a.b" = 1
+        "#,
+        )
+        .unwrap();
+
+        let res = toml.set_all("table.*", Value::Integer(2));
+
+        assert!(res.is_err());
+        assert!(is_match!(res.unwrap_err(), Error::KeyNotQueryable(_, '.')));
+    }
+
+    #[test]
+    fn test_set_same_type() {
+        let mut toml: Value = toml_from_str("[server]\nport = 8080").unwrap();
+
+        let res = toml.set_same_type("server.port", Value::String(String::from("8080")));
+        assert!(is_match!(
+            res,
+            Err(Error::CannotChangeType(_, "Integer", "String"))
+        ));
+
+        let res = toml.set_same_type("server.port", Value::Integer(80));
+        assert_eq!(res.unwrap(), Some(Value::Integer(8080)));
+
+        let res = toml.set_same_type("server.host", Value::String(String::from("localhost")));
+        assert!(res.unwrap().is_none());
+
+        let expected: Value = toml_from_str("[server]\nport = 80\nhost = \"localhost\"").unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_set_with_options_int_to_float() {
+        let mut toml: Value = toml_from_str("a = [1.5]").unwrap();
+        let options = SetOptions {
+            preserve_type: true,
+            int_to_float: true,
+            ..SetOptions::default()
+        };
+
+        let res = toml.set_with_options("a.[0]", Value::Integer(2), options);
+        assert_eq!(res.unwrap(), Some(Value::Float(1.5)));
+        assert_eq!(toml.get("a").unwrap()[0], Value::Float(2.0));
+
+        let res = toml.set_with_options("a.[0]", Value::Integer(i64::MAX), options);
+        assert!(is_match!(
+            res,
+            Err(Error::CannotChangeType(_, "Float", "Integer"))
+        ));
+
+        let res = toml.set_same_type("a.[0]", Value::Integer(2));
+        assert!(is_match!(
+            res,
+            Err(Error::CannotChangeType(_, "Float", "Integer"))
+        ));
+    }
+
+    #[test]
+    fn test_set_with_default_options_changes_type() {
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+
+        let res = toml.set_with_options("a", Value::Boolean(true), SetOptions::default());
+
+        assert_eq!(res.unwrap(), Some(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_set_or_create() {
+        let mut toml: Value = toml_from_str("[a]\nx = 1").unwrap();
+
+        let res = toml.set_or_create("a.b.c", Value::Integer(2));
+        assert!(res.unwrap().is_none());
+
+        let res = toml.set_or_create("a.b.c", Value::Integer(3));
+        assert_eq!(res.unwrap(), Some(Value::Integer(2)));
+
+        let expected: Value = toml_from_str("[a]\nx = 1\n[a.b]\nc = 3").unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_set_or_create_replaces_array_element() {
+        let mut toml: Value = toml_from_str("a = [1, 2]").unwrap();
+
+        let res = toml.set_or_create("a.[0]", Value::Integer(3));
+
+        assert_eq!(res.unwrap(), Some(Value::Integer(1)));
+        assert_eq!(toml, toml_from_str::<Value>("a = [3, 2]").unwrap());
+    }
+
+    #[test]
+    fn test_set_or_create_failure_removes_created_tables() {
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+
+        let res = toml.set_or_create("b.c.[0]", Value::Integer(1));
+        assert!(is_match!(res, Err(Error::NoIndexInTable(0))));
+
+        let res = toml.set_or_create("b.c.[0].d", Value::Integer(1));
+        assert!(is_match!(res, Err(Error::NoIndexInTable(0))));
+
+        let res = toml.set_or_create("a.b", Value::Integer(1));
+        assert!(is_match!(res, Err(Error::QueryingValueAsTable(_))));
+
+        assert_eq!(toml, toml_from_str::<Value>("a = 1").unwrap());
+    }
+
     #[cfg(feature = "typed")]
     #[test]
     fn test_serialize() {
@@ -466,4 +896,36 @@ mod test {
             _ => unreachable!(),
         }
     }
+
+    #[cfg(feature = "typed")]
+    #[test]
+    fn test_set_partial() {
+        use crate::read::{Partial, TomlValueReadExt};
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Cache {
+            size: u64,
+        }
+
+        impl<'a> Partial<'a> for Cache {
+            const LOCATION: &'static str = "services.cache";
+            type Output = Self;
+        }
+
+        let mut toml: Value = toml_from_str("[services]\nweb = 1").unwrap();
+        let cache = Cache { size: 10 };
+
+        assert!(toml.set_partial(&cache).unwrap().is_none());
+        assert_eq!(toml.read_partial::<Cache>().unwrap(), Some(cache));
+
+        let cache = Cache { size: 20 };
+        let old = toml.set_partial(&cache).unwrap();
+        assert_eq!(old, Some(toml_from_str("size = 10").unwrap()));
+        assert_eq!(toml.read_partial::<Cache>().unwrap(), Some(cache));
+        assert_eq!(toml.read("services.web").unwrap(), Some(&Value::Integer(1)));
+
+        let mut toml = Value::Table(toml::map::Map::new());
+        toml.set_partial(&Cache { size: 1 }).unwrap();
+        assert_eq!(toml, toml_from_str("[services.cache]\nsize = 1").unwrap());
+    }
 }
@@ -1,7 +1,7 @@
 /// The tokenizer for the query interpreter
 use crate::error::{Error, Result};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     Identifier {
         ident: String,
@@ -106,7 +106,7 @@ impl Token {
     pub fn identifier(&self) -> &String {
         trace!("self.identifier()");
         match self {
-            Token::Identifier { ref ident, .. } => &ident,
+            Token::Identifier { ref ident, .. } => ident,
             _ => unreachable!(),
         }
     }
@@ -259,11 +259,11 @@ mod test {
 
     #[test]
     fn test_tokenize_empty_query_to_error() {
-        let tokens = tokenize_with_seperator(&String::from(""), '.');
+        let tokens = tokenize_with_seperator("", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
-        assert!(is_match!(tokens, Error::EmptyQueryError { .. }));
+        assert!(is_match!(tokens, Error::EmptyQueryError));
     }
 
     #[test]
@@ -272,7 +272,7 @@ mod test {
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
-        assert!(is_match!(tokens, Error::EmptyIdentifier { .. }));
+        assert!(is_match!(tokens, Error::EmptyIdentifier));
     }
 
     #[test]
@@ -281,7 +281,7 @@ mod test {
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
-        assert!(is_match!(tokens, Error::ArrayAccessWithoutIndex { .. }));
+        assert!(is_match!(tokens, Error::ArrayAccessWithoutIndex));
     }
 
     #[test]
@@ -290,7 +290,7 @@ mod test {
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
-        assert!(is_match!(tokens, Error::ArrayAccessWithoutIndex { .. }));
+        assert!(is_match!(tokens, Error::ArrayAccessWithoutIndex));
     }
 
     #[test]
@@ -299,7 +299,7 @@ mod test {
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
-        assert!(is_match!(tokens, Error::ArrayAccessWithoutIndex { .. }));
+        assert!(is_match!(tokens, Error::ArrayAccessWithoutIndex));
     }
 
     #[test]
@@ -332,10 +332,7 @@ mod test {
                 ..
             } => {
                 assert_eq!("b", next.deref().identifier());
-                match next.deref() {
-                    Token::Identifier { next: None, .. } => true,
-                    _ => false,
-                }
+                matches!(next.deref(), Token::Identifier { next: None, .. })
             }
             _ => false,
         });
@@ -353,10 +350,7 @@ mod test {
             Token::Identifier {
                 next: Some(ref next),
                 ..
-            } => match next.deref() {
-                Token::Index { idx: 0, next: None } => true,
-                _ => false,
-            },
+            } => matches!(next.deref(), Token::Index { idx: 0, next: None }),
             _ => false,
         });
     }
@@ -392,15 +386,13 @@ mod test {
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
-        assert!(is_match!(tokens, Error::EmptyIdentifier { .. }));
+        assert!(is_match!(tokens, Error::EmptyIdentifier));
     }
 
     quickcheck! {
         fn test_array_index(i: usize) -> bool {
-            match tokenize_with_seperator(&format!("[{}]", i), '.') {
-                Ok(Token::Index { next: None, ..  }) => true,
-                _                                    => false,
-            }
+            matches!(tokenize_with_seperator(&format!("[{}]", i), '.'),
+                     Ok(Token::Index { next: None, ..  }))
         }
     }
 
@@ -0,0 +1,315 @@
+//! Atomic batches of modifications on a toml::Value document
+
+use toml::Value;
+
+use crate::delete::TomlValueDeleteExt;
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::read::TomlValueReadExt;
+use crate::set::{SetOptions, TomlValueSetExt};
+use crate::update::TomlValueUpdateExt;
+
+/// A batch of modifications on a document, see `TomlValueTransactionExt::transaction`
+///
+/// The `Transaction` implements the read, set, insert and delete extension traits, so it can be
+/// used like the document itself.
+///
+/// Errors of set, insert and delete operations are wrapped in `Error::TransactionFailed`, which
+/// contains the index of the failed operation within the transaction and its query.
+#[derive(Debug)]
+pub struct Transaction<'doc> {
+    document: &'doc mut Value,
+    operations: usize,
+}
+
+impl<'doc> Transaction<'doc> {
+    /// The number of set, insert and delete operations executed in this transaction so far
+    pub fn operations(&self) -> usize {
+        self.operations
+    }
+
+    fn execute<T, F>(&mut self, query: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Value) -> Result<T>,
+    {
+        let index = self.operations;
+        self.operations += 1;
+
+        f(self.document).map_err(|e| {
+            debug!(
+                "Operation {} ('{}') of transaction failed: {}",
+                index, query, e
+            );
+            Error::TransactionFailed(index, query.to_owned(), Box::new(e))
+        })
+    }
+}
+
+impl<'doc, 'tx> TomlValueReadExt<'doc> for Transaction<'tx> {
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        self.document.read_with_seperator(query, sep)
+    }
+
+    fn read_mut_with_seperator(
+        &'doc mut self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<&'doc mut Value>> {
+        self.document.read_mut_with_seperator(query, sep)
+    }
+}
+
+impl<'doc> TomlValueSetExt for Transaction<'doc> {
+    fn set_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        self.execute(query, |doc| doc.set_with_seperator(query, sep, value))
+    }
+
+    fn set_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<(String, Value)>> {
+        self.execute(pattern, |doc| {
+            doc.set_all_with_seperator(pattern, sep, value)
+        })
+    }
+
+    fn set_with_options_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>> {
+        self.execute(query, |doc| {
+            doc.set_with_options_and_seperator(query, sep, value, options)
+        })
+    }
+}
+
+impl<'doc> TomlValueInsertExt for Transaction<'doc> {
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        self.execute(query, |doc| doc.insert_with_seperator(query, sep, value))
+    }
+}
+
+impl<'doc> TomlValueDeleteExt for Transaction<'doc> {
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        self.execute(query, |doc| doc.delete_with_seperator(query, sep))
+    }
+
+    fn delete_recursive_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        prune: bool,
+    ) -> Result<Option<Value>> {
+        self.execute(query, |doc| {
+            doc.delete_recursive_with_seperator(query, sep, prune)
+        })
+    }
+
+    fn delete_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+    ) -> Result<Vec<(String, Value)>> {
+        self.execute(pattern, |doc| doc.delete_all_with_seperator(pattern, sep))
+    }
+}
+
+impl<'doc> TomlValueUpdateExt for Transaction<'doc> {
+    fn update_with_seperator<F>(&mut self, query: &str, sep: char, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        self.execute(query, |doc| doc.update_with_seperator(query, sep, f))
+    }
+
+    fn update_or_insert_with_seperator<F>(
+        &mut self,
+        query: &str,
+        sep: char,
+        default: Value,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        self.execute(query, |doc| {
+            doc.update_or_insert_with_seperator(query, sep, default, f)
+        })
+    }
+}
+
+pub trait TomlValueTransactionExt {
+    /// Extension function for modifying the current toml::Value document atomically
+    ///
+    /// # Semantics
+    ///
+    /// The function `f` gets a `Transaction` on the document, through which it can read and
+    /// modify the document. If `f` returns `Ok(_)`, all modifications are kept. If `f` returns
+    /// `Err(_)`, the document is restored to the state before the transaction, even if only some
+    /// of the modifications were made.
+    ///
+    /// # Return value
+    ///
+    /// The return value of `f` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate toml;
+    /// extern crate toml_query;
+    ///
+    /// use toml_query::error::Error;
+    /// use toml_query::set::TomlValueSetExt;
+    /// use toml_query::delete::TomlValueDeleteExt;
+    /// use toml_query::transaction::TomlValueTransactionExt;
+    ///
+    /// let mut toml : toml::Value = toml::from_str("[table]\na = 1").unwrap();
+    /// let before = toml.clone();
+    ///
+    /// let res = toml.transaction(|tx| {
+    ///     tx.set("table.a", toml::Value::Integer(2))?;
+    ///     tx.delete("table.b.c")?; // fails, there is no table "b"
+    ///     Ok(())
+    /// });
+    ///
+    /// match res {
+    ///     Err(Error::TransactionFailed(index, query, _)) => {
+    ///         assert_eq!(index, 1);
+    ///         assert_eq!(query, "table.b.c");
+    ///     }
+    ///     _ => panic!("Expected the transaction to fail"),
+    /// }
+    /// assert_eq!(toml, before);
+    /// ```
+    ///
+    fn transaction<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<T>;
+}
+
+impl TomlValueTransactionExt for Value {
+    fn transaction<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<T>,
+    {
+        let backup = self.clone();
+
+        let result = f(&mut Transaction {
+            document: self,
+            operations: 0,
+        });
+
+        if result.is_err() {
+            debug!("Transaction failed, restoring document");
+            *self = backup;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_transaction_applies_all_operations() {
+        let mut toml: Value = toml_from_str("[table]\na = 1\nb = 2").unwrap();
+
+        let res = toml.transaction(|tx| {
+            tx.set("table.a", Value::Integer(3))?;
+            tx.insert("other.c", Value::Integer(4))?;
+            tx.delete("table.b")?;
+            Ok(tx.operations())
+        });
+
+        assert_eq!(res.unwrap(), 3);
+        let expected: Value = toml_from_str("[table]\na = 3\n[other]\nc = 4").unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_transaction_restores_document_on_failure() {
+        let mut toml: Value = toml_from_str("[table]\na = 1\narray = [1]").unwrap();
+        let before = toml.clone();
+
+        let res = toml.transaction(|tx| {
+            tx.set("table.a", Value::Integer(3))?;
+            tx.insert("other.c", Value::Integer(4))?;
+            tx.delete("table.array")?; // non-empty array
+            tx.set("table.a", Value::Integer(5))?;
+            Ok(())
+        });
+
+        let err = res.unwrap_err();
+        match err {
+            Error::TransactionFailed(index, ref query, ref cause) => {
+                assert_eq!(index, 2);
+                assert_eq!(query, "table.array");
+                assert!(is_match!(**cause, Error::CannotDeleteNonEmptyArray(_)));
+            }
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+        assert_eq!(toml, before);
+    }
+
+    #[test]
+    fn test_transaction_restores_document_on_user_error() {
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+        let before = toml.clone();
+
+        let res: Result<()> = toml.transaction(|tx| {
+            tx.set("a", Value::Integer(2))?;
+            Err(Error::NotAvailable(String::from("b")))
+        });
+
+        assert!(is_match!(res.unwrap_err(), Error::NotAvailable(_)));
+        assert_eq!(toml, before);
+    }
+
+    #[test]
+    fn test_transaction_reverts_failed_update() {
+        let mut toml: Value = toml_from_str("a = [1]").unwrap();
+        let before = toml.clone();
+
+        let res = toml.transaction(|tx| {
+            tx.update("a", |a| {
+                *a = Value::Array(vec![]);
+                Err(Error::NotAvailable(String::from("b")))
+            })
+        });
+
+        assert!(is_match!(res, Err(Error::TransactionFailed(0, _, _))));
+        assert_eq!(toml, before);
+    }
+
+    #[test]
+    fn test_transaction_reads_own_modifications() {
+        use crate::read::TomlValueReadTypeExt;
+
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+
+        let res = toml.transaction(|tx| {
+            tx.set("a", Value::Integer(2))?;
+            tx.read_int("a")
+        });
+
+        assert_eq!(res.unwrap(), Some(2));
+    }
+}
@@ -0,0 +1,373 @@
+//! The Toml Update extensions
+
+#[cfg(feature = "typed")]
+use serde::Serialize;
+use toml::Value;
+
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::read::TomlValueReadExt;
+
+pub trait TomlValueUpdateExt {
+    /// Extension function for modifying a value in place in the current toml::Value document
+    /// using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// The function `f` is called with the value at `query` and may modify it in any way. The
+    /// function _never_ creates values, see `TomlValueUpdateExt::update_or_insert_with_seperator`
+    /// for that.
+    ///
+    /// If `f` returns an error, the modifications it made to the value so far are kept. Use a
+    /// transaction if they should be reverted.
+    ///
+    /// # Return value
+    ///
+    /// * If the update operation worked correctly, `Ok(())` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If there is no value at `query`: error
+    ///     * If the query cannot be resolved (see `TomlValueReadExt`): error
+    ///     * If `f` returns an error: that error
+    ///
+    fn update_with_seperator<F>(&mut self, query: &str, sep: char, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>;
+
+    /// Extension function for modifying a value in place in the current toml::Value document
+    ///
+    /// See documentation of `TomlValueUpdateExt::update_with_seperator`
+    fn update<F>(&mut self, query: &str, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        self.update_with_seperator(query, '.', f)
+    }
+
+    /// Extension function for modifying a value in place in the current toml::Value document,
+    /// or inserting a default value if there is none, using a custom seperator
+    ///
+    /// # Semantics
+    ///
+    /// If there is a value at `query`, it is modified by `f` like with
+    /// `TomlValueUpdateExt::update_with_seperator`. Otherwise `default` is inserted like with
+    /// `TomlValueInsertExt::insert_with_seperator` and `f` is not called.
+    ///
+    /// # Return value
+    ///
+    /// * If the update or insert operation worked correctly, `Ok(())` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If the query cannot be resolved or the default cannot be inserted: error
+    ///     * If `f` returns an error: that error
+    ///
+    fn update_or_insert_with_seperator<F>(
+        &mut self,
+        query: &str,
+        sep: char,
+        default: Value,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>;
+
+    /// Extension function for modifying a value in place in the current toml::Value document,
+    /// or inserting a default value if there is none
+    ///
+    /// See documentation of `TomlValueUpdateExt::update_or_insert_with_seperator`
+    fn update_or_insert<F>(&mut self, query: &str, default: Value, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        self.update_or_insert_with_seperator(query, '.', default, f)
+    }
+
+    /// Extension function for serializing a value into the value at `query` in the current
+    /// toml::Value document
+    ///
+    /// # Semantics
+    ///
+    /// The serialized value replaces the value at `query`. Unlike
+    /// `TomlValueSetExt::set_serialized`, it is written directly into the document. The function
+    /// _never_ creates values, like `TomlValueUpdateExt::update_with_seperator`. If serializing
+    /// fails, the value at `query` is not modified.
+    ///
+    /// # Return value
+    ///
+    /// * If the update operation worked correctly, `Ok(())` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If there is no value at `query`: error
+    ///     * If the query cannot be resolved (see `TomlValueReadExt`): error
+    ///     * If the value cannot be serialized: error
+    ///
+    #[cfg(feature = "typed")]
+    fn update_serialized<S: Serialize + ?Sized>(&mut self, query: &str, value: &S) -> Result<()> {
+        use crate::serializer::ValueSerializer;
+
+        self.update(query, |target| {
+            value
+                .serialize(ValueSerializer::new(target))
+                .map_err(Error::TomlSerialize)
+        })
+    }
+
+    /// Extension function for merging a serialized value into the value at `query` in the
+    /// current toml::Value document
+    ///
+    /// # Semantics
+    ///
+    /// Structs and maps are merged into the table at `query` key by key: keys which are not
+    /// serialized, for example because the field is `None` or skipped, keep their value. Nested
+    /// structs and maps are merged the same way, all other values are replaced. See
+    /// `serializer::ValueSerializer::merging`.
+    ///
+    /// If serializing fails, the value at `query` is not modified.
+    ///
+    /// # Return value
+    ///
+    /// * If the merge operation worked correctly, `Ok(())` is returned.
+    /// * On failure, `Err(e)` is returned:
+    ///     * If there is no value at `query`: error
+    ///     * If the query cannot be resolved (see `TomlValueReadExt`): error
+    ///     * If the value cannot be serialized: error
+    ///
+    #[cfg(feature = "typed")]
+    fn merge_serialized<S: Serialize + ?Sized>(&mut self, query: &str, value: &S) -> Result<()> {
+        use crate::serializer::ValueSerializer;
+
+        self.update(query, |target| {
+            value
+                .serialize(ValueSerializer::merging(target))
+                .map_err(Error::TomlSerialize)
+        })
+    }
+}
+
+impl TomlValueUpdateExt for Value {
+    fn update_with_seperator<F>(&mut self, query: &str, sep: char, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        match self.read_mut_with_seperator(query, sep)? {
+            Some(value) => f(value),
+            None => Err(Error::NotAvailable(query.to_owned())),
+        }
+    }
+
+    fn update_or_insert_with_seperator<F>(
+        &mut self,
+        query: &str,
+        sep: char,
+        default: Value,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        match self.read_mut_with_seperator(query, sep)? {
+            Some(value) => f(value),
+            None => self.insert_with_seperator(query, sep, default).map(|_| ()),
+        }
+    }
+}
+
+/// Extension trait for modifying values of a specific type in place
+///
+/// The functions fail with `Error::TypeError` if the value at the query has another type, and
+/// with `Error::NotAvailable` if there is no value at the query.
+pub trait TomlValueUpdateTypeExt: TomlValueUpdateExt {
+    fn update_string<F: FnOnce(String) -> String>(&mut self, query: &str, f: F) -> Result<()>;
+    fn update_int<F: FnOnce(i64) -> i64>(&mut self, query: &str, f: F) -> Result<()>;
+    fn update_float<F: FnOnce(f64) -> f64>(&mut self, query: &str, f: F) -> Result<()>;
+    fn update_bool<F: FnOnce(bool) -> bool>(&mut self, query: &str, f: F) -> Result<()>;
+}
+
+macro_rules! make_type_updater {
+    ($fnname:ident, $type:ty, $typename:expr, $variant:path) => {
+        fn $fnname<F: FnOnce($type) -> $type>(&mut self, query: &str, f: F) -> Result<()> {
+            self.update_with_seperator(query, '.', |value| match value {
+                $variant(ref mut obj) => {
+                    *obj = f(std::mem::take(obj));
+                    Ok(())
+                }
+                other => Err(Error::TypeError($typename, crate::util::name_of_val(other))),
+            })
+        }
+    };
+}
+
+impl<T> TomlValueUpdateTypeExt for T
+where
+    T: TomlValueUpdateExt,
+{
+    make_type_updater!(update_string, String, "String", Value::String);
+    make_type_updater!(update_int, i64, "Integer", Value::Integer);
+    make_type_updater!(update_float, f64, "Float", Value::Float);
+    make_type_updater!(update_bool, bool, "Boolean", Value::Boolean);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_update_value() {
+        let mut toml: Value = toml_from_str("[table]\narray = [1, 2]").unwrap();
+
+        let res = toml.update("table.array", |array| match array {
+            Value::Array(ref mut a) => {
+                a.push(Value::Integer(3));
+                Ok(())
+            }
+            _ => panic!("Expected an array"),
+        });
+
+        assert!(res.is_ok());
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[table]\narray = [1, 2, 3]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_update_nonexistent_value() {
+        let mut toml: Value = toml_from_str("[table]").unwrap();
+
+        let res = toml.update("table.a", |_| panic!("Must not be called"));
+
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+        assert_eq!(toml, toml_from_str::<Value>("[table]").unwrap());
+    }
+
+    #[test]
+    fn test_update_passes_through_error() {
+        let mut toml: Value = toml_from_str("a = 1").unwrap();
+
+        let res = toml.update("a", |_| Err(Error::EmptyQueryError));
+
+        assert!(is_match!(res, Err(Error::EmptyQueryError)));
+    }
+
+    #[test]
+    fn test_update_or_insert_existing_value() {
+        let mut toml: Value = toml_from_str("[table]\na = 1").unwrap();
+
+        let res = toml.update_or_insert("table.a", Value::Integer(0), |v| {
+            *v = Value::Integer(2);
+            Ok(())
+        });
+
+        assert!(res.is_ok());
+        assert_eq!(toml, toml_from_str::<Value>("[table]\na = 2").unwrap());
+    }
+
+    #[test]
+    fn test_update_or_insert_creates_tables() {
+        let mut toml: Value = toml_from_str("").unwrap();
+
+        let res =
+            toml.update_or_insert("a.b.c", Value::Integer(0), |_| panic!("Must not be called"));
+
+        assert!(res.is_ok());
+        assert_eq!(toml, toml_from_str::<Value>("[a.b]\nc = 0").unwrap());
+    }
+
+    #[test]
+    fn test_update_int() {
+        let mut toml: Value = toml_from_str("[counter]\nvalue = 41").unwrap();
+
+        toml.update_int("counter.value", |i| i + 1).unwrap();
+
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[counter]\nvalue = 42").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_update_string_in_array() {
+        let mut toml: Value = toml_from_str("names = [\"a\", \"b\"]").unwrap();
+
+        toml.update_string("names.[1]", |s| s + "c").unwrap();
+
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("names = [\"a\", \"bc\"]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_update_float_and_bool() {
+        let mut toml: Value = toml_from_str("f = 1.5\nb = true").unwrap();
+
+        toml.update_float("f", |f| f * 2.0).unwrap();
+        toml.update_bool("b", |b| !b).unwrap();
+
+        assert_eq!(toml, toml_from_str::<Value>("f = 3.0\nb = false").unwrap());
+    }
+
+    #[test]
+    fn test_update_int_type_mismatch() {
+        let mut toml: Value = toml_from_str("a = \"1\"").unwrap();
+
+        let res = toml.update_int("a", |i| i + 1);
+
+        assert!(is_match!(res, Err(Error::TypeError("Integer", "String"))));
+        assert_eq!(toml, toml_from_str::<Value>("a = \"1\"").unwrap());
+    }
+
+    #[cfg(feature = "typed")]
+    #[test]
+    fn test_update_and_merge_serialized() {
+        #[derive(Serialize)]
+        struct Server {
+            host: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            port: Option<u16>,
+        }
+
+        let server = Server {
+            host: String::from("example.com"),
+            port: None,
+        };
+
+        let mut toml: Value = toml_from_str("[server]\nhost = \"localhost\"\nport = 80").unwrap();
+        toml.merge_serialized("server", &server).unwrap();
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[server]\nhost = \"example.com\"\nport = 80").unwrap()
+        );
+
+        toml.update_serialized("server", &server).unwrap();
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[server]\nhost = \"example.com\"").unwrap()
+        );
+
+        let res = toml.merge_serialized("client", &server);
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+    }
+
+    #[cfg(feature = "typed")]
+    #[test]
+    fn test_update_serialized_failure_keeps_value() {
+        #[derive(Serialize)]
+        struct S {
+            a: &'static str,
+            b: u64,
+        }
+
+        let mut toml: Value = toml_from_str("[s]\na = \"x\"\nb = 1\nc = 2").unwrap();
+        let before = toml.clone();
+
+        let res = toml.update_serialized(
+            "s",
+            &S {
+                a: "y",
+                b: u64::MAX,
+            },
+        );
+
+        assert!(is_match!(res, Err(Error::TomlSerialize(_))));
+        assert_eq!(toml, before);
+    }
+}
@@ -1,5 +1,8 @@
 use toml::Value;
 
+use crate::error::{Error, Result};
+use crate::tokenizer::Token;
+
 pub fn name_of_val(val: &Value) -> &'static str {
     match *val {
         Value::Array(_) => "Array",
@@ -11,3 +14,121 @@ pub fn name_of_val(val: &Value) -> &'static str {
         Value::Table(_) => "Table",
     }
 }
+
+/// Build the query for a key in the table at `prefix`
+pub fn key_query(prefix: Option<&str>, key: &str, sep: char) -> Result<String> {
+    let is_index = key.starts_with('[') && key.ends_with(']');
+    if key.is_empty() || key.contains(sep) || is_index {
+        return Err(Error::KeyNotQueryable(key.to_owned(), sep));
+    }
+
+    Ok(match prefix {
+        Some(prefix) => format!("{}{}{}", prefix, sep, key),
+        None => key.to_owned(),
+    })
+}
+
+/// Build the query for an index in the array at `prefix`
+pub fn index_query(prefix: Option<&str>, idx: usize, sep: char) -> String {
+    match prefix {
+        Some(prefix) => format!("{}{}[{}]", prefix, sep, idx),
+        None => format!("[{}]", idx),
+    }
+}
+
+/// A step in a path to a value in a document
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Build a query from a path, which must not be empty
+pub fn path_to_query(path: &[Segment], sep: char) -> Result<String> {
+    let mut query: Option<String> = None;
+    for segment in path {
+        let prefix = query.as_deref();
+        query = Some(match segment {
+            Segment::Key(key) => key_query(prefix, key, sep)?,
+            Segment::Index(idx) => index_query(prefix, *idx, sep),
+        });
+    }
+
+    query.ok_or(Error::EmptyQueryError)
+}
+
+/// Get the path a chain of tokens describes
+pub fn token_path(tokens: &Token) -> Vec<Segment> {
+    let mut path = vec![];
+    let mut current = Some(tokens);
+    while let Some(token) = current {
+        path.push(match token {
+            Token::Identifier { ref ident, .. } => Segment::Key(ident.clone()),
+            Token::Index { idx, .. } => Segment::Index(*idx),
+        });
+        current = token.next();
+    }
+    path
+}
+
+/// Get the value at a path, if it exists
+pub fn get_path<'doc>(mut doc: &'doc Value, path: &[Segment]) -> Option<&'doc Value> {
+    for segment in path {
+        doc = match (doc, segment) {
+            (Value::Table(ref t), Segment::Key(ref key)) => t.get(key)?,
+            (Value::Array(ref a), Segment::Index(idx)) => a.get(*idx)?,
+            _ => return None,
+        };
+    }
+    Some(doc)
+}
+
+/// Get the value at a path mutably, if it exists
+pub fn get_path_mut<'doc>(mut doc: &'doc mut Value, path: &[Segment]) -> Option<&'doc mut Value> {
+    for segment in path {
+        doc = match (doc, segment) {
+            (Value::Table(ref mut t), Segment::Key(ref key)) => t.get_mut(key)?,
+            (Value::Array(ref mut a), Segment::Index(idx)) => a.get_mut(*idx)?,
+            _ => return None,
+        };
+    }
+    Some(doc)
+}
+
+/// Remove the value at a path, even if it is a non-empty structure
+///
+/// Returns `None` if there is no value at the path.
+pub fn remove_path(doc: &mut Value, path: &[Segment]) -> Option<Value> {
+    let (last, parent) = path.split_last()?;
+    match (get_path_mut(doc, parent), last) {
+        (Some(Value::Table(ref mut t)), Segment::Key(ref key)) => t.remove(key),
+        (Some(Value::Array(ref mut a)), Segment::Index(idx)) if *idx < a.len() => {
+            Some(a.remove(*idx))
+        }
+        _ => None,
+    }
+}
+
+/// Insert a value at a path, shifting array elements
+///
+/// The parent of the path must exist. If the index of an array is out of bounds or the parent
+/// has the wrong type, the value is given back as error.
+pub fn insert_path(
+    doc: &mut Value,
+    path: &[Segment],
+    value: Value,
+) -> std::result::Result<Option<Value>, Value> {
+    let (last, parent) = match path.split_last() {
+        Some(split) => split,
+        None => return Err(value),
+    };
+
+    match (get_path_mut(doc, parent), last) {
+        (Some(Value::Table(ref mut t)), Segment::Key(ref key)) => Ok(t.insert(key.clone(), value)),
+        (Some(Value::Array(ref mut a)), Segment::Index(idx)) if *idx <= a.len() => {
+            a.insert(*idx, value);
+            Ok(None)
+        }
+        _ => Err(value),
+    }
+}
@@ -0,0 +1,586 @@
+//! Validation of modifications on a toml::Value document
+
+use std::iter::once;
+
+use toml::Value;
+
+use crate::array::TomlValueArrayExt;
+use crate::delete::TomlValueDeleteExt;
+use crate::error::{Error, Result};
+use crate::insert::TomlValueInsertExt;
+use crate::patch::{Operation, Patch, TomlValuePatchExt};
+use crate::read::TomlValueReadExt;
+use crate::restructure::TomlValueRestructureExt;
+use crate::set::{SetOptions, TomlValueSetExt};
+use crate::tokenizer::tokenize_with_seperator;
+use crate::update::TomlValueUpdateExt;
+use crate::util::{get_path, name_of_val, path_to_query, token_path, Segment};
+
+/// A document which rejects modifications producing invalid TOML, see
+/// `TomlValueValidateExt::validated`
+///
+/// TOML requires the values of an array to be of the same type. Arrays of arrays and arrays of
+/// tables are allowed, even if the inner arrays and tables differ. Every set, insert, update,
+/// array, restructure and patch operation checks the arrays it modifies and the arrays in the
+/// values it writes, and fails with `Error::HeterogeneousArray` before modifying the document
+/// if one of them would become heterogeneous.
+///
+/// Functions which hand out mutable references, like `TomlValueReadExt::read_mut` and
+/// `TomlValueArrayExt::array_mut`, cannot be validated.
+#[derive(Debug)]
+pub struct Validated<'doc> {
+    document: &'doc mut Value,
+}
+
+impl<'doc> Validated<'doc> {
+    /// The validated document
+    pub fn document(&self) -> &Value {
+        self.document
+    }
+
+    /// Check writing `value` at `query`, either replacing the value there or shifting it
+    fn check_write(&self, query: &str, sep: char, value: &Value, replaces: bool) -> Result<()> {
+        let path = token_path(&tokenize_with_seperator(query, sep)?);
+        self.check_write_path(path, sep, value, replaces)
+    }
+
+    fn check_write_path(
+        &self,
+        mut path: Vec<Segment>,
+        sep: char,
+        value: &Value,
+        replaces: bool,
+    ) -> Result<()> {
+        check_value(value, &mut path, sep)?;
+
+        if let Some(Segment::Index(idx)) = path.pop() {
+            if let Some(Value::Array(ref array)) = get_path(self.document, &path) {
+                let others = array
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !replaces || *i != idx)
+                    .map(|(_, v)| v);
+                check_elements(others.chain(once(value)), &path, sep)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check replacing the values at all `paths` with `value`
+    fn check_replace_all(&self, paths: &[Vec<Segment>], sep: char, value: &Value) -> Result<()> {
+        for path in paths {
+            let mut path = path.clone();
+            check_value(value, &mut path, sep)?;
+
+            if let Some(Segment::Index(_)) = path.pop() {
+                if let Some(Value::Array(ref array)) = get_path(self.document, &path) {
+                    let replaced = paths
+                        .iter()
+                        .filter(|p| p.len() == path.len() + 1 && p.starts_with(&path))
+                        .filter_map(|p| match p.last() {
+                            Some(Segment::Index(idx)) => Some(*idx),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    let others = array
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !replaced.contains(i))
+                        .map(|(_, v)| v);
+                    check_elements(others.chain(once(value)), &path, sep)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Describe a path in an error, even if it cannot be expressed in a query
+fn describe(path: &[Segment], sep: char) -> String {
+    path_to_query(path, sep).unwrap_or_else(|_| format!("{:?}", path))
+}
+
+/// Check that the values of the array at `path` are all of the same type
+fn check_elements<'a, I>(mut values: I, path: &[Segment], sep: char) -> Result<()>
+where
+    I: Iterator<Item = &'a Value>,
+{
+    if let Some(first) = values.next().map(name_of_val) {
+        if let Some(other) = values.map(name_of_val).find(|t| *t != first) {
+            return Err(Error::HeterogeneousArray(describe(path, sep), first, other));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check all arrays in `value`, which is located at `path`
+fn check_value(value: &Value, path: &mut Vec<Segment>, sep: char) -> Result<()> {
+    match value {
+        Value::Array(ref array) => {
+            check_elements(array.iter(), path, sep)?;
+            for (idx, value) in array.iter().enumerate() {
+                path.push(Segment::Index(idx));
+                check_value(value, path, sep)?;
+                path.pop();
+            }
+        }
+        Value::Table(ref table) => {
+            for (key, value) in table.iter() {
+                path.push(Segment::Key(key.clone()));
+                check_value(value, path, sep)?;
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+impl<'doc, 'v> TomlValueReadExt<'doc> for Validated<'v> {
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        self.document.read_with_seperator(query, sep)
+    }
+
+    fn read_mut_with_seperator(
+        &'doc mut self,
+        query: &str,
+        sep: char,
+    ) -> Result<Option<&'doc mut Value>> {
+        self.document.read_mut_with_seperator(query, sep)
+    }
+}
+
+impl<'doc> TomlValueSetExt for Validated<'doc> {
+    fn set_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        self.check_write(query, sep, &value, true)?;
+        self.document.set_with_seperator(query, sep, value)
+    }
+
+    fn set_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Vec<(String, Value)>> {
+        use crate::resolver::multi_resolver::resolve_outermost;
+
+        let tokens = tokenize_with_seperator(pattern, sep)?;
+        let paths = resolve_outermost(self.document, &tokens);
+        self.check_replace_all(&paths, sep, &value)?;
+        self.document.set_all_with_seperator(pattern, sep, value)
+    }
+
+    fn set_with_options_and_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+        options: SetOptions,
+    ) -> Result<Option<Value>> {
+        let value = options.prepare(self.document, query, sep, value)?;
+        self.check_write(query, sep, &value, true)?;
+        self.document
+            .set_with_options_and_seperator(query, sep, value, options)
+    }
+}
+
+impl<'doc> TomlValueInsertExt for Validated<'doc> {
+    fn insert_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        self.check_write(query, sep, &value, false)?;
+        self.document.insert_with_seperator(query, sep, value)
+    }
+}
+
+impl<'doc> TomlValueDeleteExt for Validated<'doc> {
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        self.document.delete_with_seperator(query, sep)
+    }
+
+    fn delete_recursive_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        prune: bool,
+    ) -> Result<Option<Value>> {
+        self.document
+            .delete_recursive_with_seperator(query, sep, prune)
+    }
+
+    fn delete_all_with_seperator(
+        &mut self,
+        pattern: &str,
+        sep: char,
+    ) -> Result<Vec<(String, Value)>> {
+        self.document.delete_all_with_seperator(pattern, sep)
+    }
+}
+
+impl<'doc> TomlValueUpdateExt for Validated<'doc> {
+    fn update_with_seperator<F>(&mut self, query: &str, sep: char, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        // Modify a copy, so the document is not modified if the result is invalid
+        let mut value = self
+            .document
+            .read_with_seperator(query, sep)?
+            .cloned()
+            .ok_or_else(|| Error::NotAvailable(query.to_owned()))?;
+
+        f(&mut value)?;
+        self.set_with_seperator(query, sep, value).map(|_| ())
+    }
+
+    fn update_or_insert_with_seperator<F>(
+        &mut self,
+        query: &str,
+        sep: char,
+        default: Value,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        if self.document.read_with_seperator(query, sep)?.is_some() {
+            self.update_with_seperator(query, sep, f)
+        } else {
+            self.insert_with_seperator(query, sep, default).map(|_| ())
+        }
+    }
+}
+
+impl<'doc> TomlValueArrayExt for Validated<'doc> {
+    fn array_mut_with_seperator(&mut self, query: &str, sep: char) -> Result<&mut Vec<Value>> {
+        self.document.array_mut_with_seperator(query, sep)
+    }
+
+    fn push_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<()> {
+        let len = self.document.array_mut_with_seperator(query, sep)?.len();
+        self.insert_at_with_seperator(query, sep, len, value)
+    }
+
+    fn insert_at_with_seperator(
+        &mut self,
+        query: &str,
+        sep: char,
+        idx: usize,
+        value: Value,
+    ) -> Result<()> {
+        let mut path = token_path(&tokenize_with_seperator(query, sep)?);
+        path.push(Segment::Index(idx));
+        self.check_write_path(path, sep, &value, false)?;
+        self.document
+            .insert_at_with_seperator(query, sep, idx, value)
+    }
+}
+
+impl<'doc> TomlValueRestructureExt for Validated<'doc> {
+    fn move_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        if let Some(value) = self.document.read_with_seperator(from, sep)? {
+            self.check_write(to, sep, value, false)?;
+        }
+        self.document.move_value_with_seperator(from, to, sep)
+    }
+
+    fn copy_value_with_seperator(
+        &mut self,
+        from: &str,
+        to: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        if let Some(value) = self.document.read_with_seperator(from, sep)? {
+            self.check_write(to, sep, value, false)?;
+        }
+        self.document.copy_value_with_seperator(from, to, sep)
+    }
+
+    fn rename_key_with_seperator(
+        &mut self,
+        query: &str,
+        new_name: &str,
+        sep: char,
+    ) -> Result<Option<Value>> {
+        self.document
+            .rename_key_with_seperator(query, new_name, sep)
+    }
+}
+
+impl<'doc> TomlValuePatchExt for Validated<'doc> {
+    /// Unlike `TomlValuePatchExt::apply_patch` for toml::Value, the patch is applied
+    /// completely or not at all
+    fn apply_patch(&mut self, patch: &Patch) -> Result<()> {
+        let sep = patch.seperator();
+        let mut document = self.document.clone();
+        document.apply_patch(patch)?;
+
+        // Only the values the patch writes and the arrays they are in are checked. The operations
+        // never shift array elements, so their paths are still valid after applying the patch.
+        for operation in patch.operations() {
+            if let Operation::Delete { .. } = operation {
+                continue;
+            }
+
+            let mut path = token_path(&tokenize_with_seperator(operation.query(), sep)?);
+            if let Some(value) = get_path(&document, &path) {
+                check_value(value, &mut path.clone(), sep)?;
+            }
+            if let Some(Segment::Index(_)) = path.pop() {
+                if let Some(Value::Array(ref array)) = get_path(&document, &path) {
+                    check_elements(array.iter(), &path, sep)?;
+                }
+            }
+        }
+
+        *self.document = document;
+        Ok(())
+    }
+}
+
+pub trait TomlValueValidateExt {
+    /// Extension function for modifying the current toml::Value document with validation
+    ///
+    /// See documentation of `Validated`
+    fn validated(&mut self) -> Validated<'_>;
+
+    /// Extension function for checking that all arrays in the current toml::Value document are
+    /// homogeneous, naming the path of an invalid array with a custom seperator
+    fn check_arrays_with_seperator(&self, sep: char) -> Result<()>;
+
+    /// Extension function for checking that all arrays in the current toml::Value document are
+    /// homogeneous
+    ///
+    /// See documentation of `TomlValueValidateExt::check_arrays_with_seperator`
+    fn check_arrays(&self) -> Result<()> {
+        self.check_arrays_with_seperator('.')
+    }
+}
+
+impl TomlValueValidateExt for Value {
+    fn validated(&mut self) -> Validated<'_> {
+        Validated { document: self }
+    }
+
+    fn check_arrays_with_seperator(&self, sep: char) -> Result<()> {
+        check_value(self, &mut vec![], sep)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn assert_heterogeneous<T: std::fmt::Debug>(res: Result<T>, path: &str) {
+        match res {
+            Err(Error::HeterogeneousArray(ref p, _, _)) => assert_eq!(p, path),
+            _ => panic!("Expected a heterogeneous array error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_set_rejects_other_type() {
+        let mut toml: Value = toml_from_str("[table]\na = [\"x\", \"y\"]").unwrap();
+
+        let res = toml.validated().set("table.a.[1]", Value::Integer(1));
+
+        match res {
+            Err(Error::HeterogeneousArray(ref path, "String", "Integer")) => {
+                assert_eq!(path, "table.a")
+            }
+            _ => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("[table]\na = [\"x\", \"y\"]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_set_replaces_only_element() {
+        let mut toml: Value = toml_from_str("a = [\"x\"]").unwrap();
+
+        toml.validated().set("a.[0]", Value::Integer(1)).unwrap();
+
+        assert_eq!(toml, toml_from_str::<Value>("a = [1]").unwrap());
+    }
+
+    #[test]
+    fn test_set_all_replaces_all_elements() {
+        let mut toml: Value = toml_from_str("a = [\"x\", \"y\"]").unwrap();
+
+        toml.validated().set_all("a.*", Value::Integer(1)).unwrap();
+
+        assert_eq!(toml, toml_from_str::<Value>("a = [1, 1]").unwrap());
+    }
+
+    #[test]
+    fn test_insert_rejects_other_type() {
+        let mut toml: Value = toml_from_str("a = [1, 2]").unwrap();
+
+        let res = toml.validated().insert("a.[0]", Value::Boolean(true));
+
+        assert_heterogeneous(res, "a");
+        assert_eq!(toml, toml_from_str::<Value>("a = [1, 2]").unwrap());
+    }
+
+    #[test]
+    fn test_insert_rejects_heterogeneous_value() {
+        let mut toml: Value = toml_from_str("").unwrap();
+        let value = Value::Array(vec![Value::Integer(1), Value::Float(1.0)]);
+
+        let res = toml.validated().insert("a.b", Value::Array(vec![value]));
+
+        assert_heterogeneous(res, "a.b.[0]");
+        assert_eq!(toml, toml_from_str::<Value>("").unwrap());
+    }
+
+    #[test]
+    fn test_nested_arrays_may_differ() {
+        let mut toml: Value = toml_from_str("a = [[1], [\"x\"]]\nb = [{ x = 1 }]").unwrap();
+
+        let mut validated = toml.validated();
+        validated
+            .push("a", Value::Array(vec![Value::Boolean(true)]))
+            .unwrap();
+        validated
+            .push("b", toml_from_str::<Value>("y = \"z\"").unwrap())
+            .unwrap();
+
+        assert_eq!(
+            toml,
+            toml_from_str::<Value>("a = [[1], [\"x\"], [true]]\nb = [{ x = 1 }, { y = \"z\" }]")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_array_and_update_operations() {
+        use crate::update::TomlValueUpdateTypeExt;
+
+        let mut toml: Value = toml_from_str("a = [1, 2]").unwrap();
+        let mut validated = toml.validated();
+
+        assert_heterogeneous(validated.push("a", Value::Float(1.0)), "a");
+        assert_heterogeneous(validated.insert_at("a", 0, Value::Float(1.0)), "a");
+        assert_heterogeneous(
+            validated.update("a.[0]", |v| {
+                *v = Value::Float(1.0);
+                Ok(())
+            }),
+            "a",
+        );
+        validated.update_int("a.[0]", |i| i + 1).unwrap();
+
+        assert_eq!(toml, toml_from_str::<Value>("a = [2, 2]").unwrap());
+    }
+
+    #[test]
+    fn test_array_operations_with_seperator() {
+        let mut toml: Value = toml_from_str("[t]\na = [1]").unwrap();
+        let mut validated = toml.validated();
+
+        assert_heterogeneous(
+            validated.push_with_seperator("t/a", '/', Value::Boolean(true)),
+            "t/a",
+        );
+        assert_heterogeneous(
+            validated.insert_at_with_seperator("t/a", '/', 0, Value::Boolean(true)),
+            "t/a",
+        );
+        validated
+            .push_with_seperator("t/a", '/', Value::Integer(2))
+            .unwrap();
+
+        assert_eq!(toml, toml_from_str::<Value>("[t]\na = [1, 2]").unwrap());
+    }
+
+    #[test]
+    fn test_copy_rejects_other_type() {
+        let mut toml: Value = toml_from_str("a = [1]\nb = \"x\"").unwrap();
+
+        let res = toml.validated().copy_value("b", "a.[1]");
+
+        assert_heterogeneous(res, "a");
+        assert_eq!(toml, toml_from_str::<Value>("a = [1]\nb = \"x\"").unwrap());
+    }
+
+    #[test]
+    fn test_patch_is_validated() {
+        let base: Value = toml_from_str("a = [1]").unwrap();
+        let mut target: Value = toml_from_str("a = [1]").unwrap();
+        target.as_table_mut().unwrap().insert(
+            String::from("a"),
+            Value::Array(vec![Value::Integer(1), Value::Boolean(true)]),
+        );
+        let patch = Patch::diff(&base, &target).unwrap();
+
+        let mut toml = base.clone();
+        let res = toml.validated().apply_patch(&patch);
+
+        assert_heterogeneous(res, "a");
+        assert_eq!(toml, base);
+    }
+
+    #[test]
+    fn test_patch_checks_only_touched_arrays() {
+        let mut base: Value = toml_from_str("a = [1]\nb = 1").unwrap();
+        base.as_table_mut().unwrap().insert(
+            String::from("m"),
+            Value::Array(vec![Value::Integer(1), Value::Boolean(true)]),
+        );
+        let mut target = base.clone();
+        target.set("a.[1]", Value::Integer(2)).unwrap();
+        target.set("b", Value::Integer(2)).unwrap();
+        let patch = Patch::diff(&base, &target).unwrap();
+
+        let mut toml = base.clone();
+        toml.validated().apply_patch(&patch).unwrap();
+
+        assert_eq!(toml, target);
+    }
+
+    #[test]
+    fn test_patch_may_change_element_types() {
+        let base: Value = toml_from_str("a = [1, 2]").unwrap();
+        let target: Value = toml_from_str("a = [\"x\", \"y\"]").unwrap();
+        let patch = Patch::diff(&base, &target).unwrap();
+
+        let mut toml = base.clone();
+        toml.validated().apply_patch(&patch).unwrap();
+
+        assert_eq!(toml, target);
+    }
+
+    #[test]
+    fn test_check_arrays() {
+        assert!(toml_from_str::<Value>("a = [[1], [\"x\"]]")
+            .unwrap()
+            .check_arrays()
+            .is_ok());
+
+        let mut toml: Value = toml_from_str("[t]").unwrap();
+        toml.set("t.a", Value::Array(vec![Value::Integer(1)]))
+            .unwrap();
+        toml.set("t.a.[1]", Value::Boolean(false)).unwrap();
+        assert_heterogeneous(toml.check_arrays_with_seperator('/'), "t/a");
+    }
+}
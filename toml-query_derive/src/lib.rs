@@ -1,45 +1,367 @@
 #![warn(rust_2018_idioms)]
 
-extern crate proc_macro;
-
-#[cfg(test)]
-#[macro_use]
-extern crate serde_derive;
-
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{Lit, Meta, MetaNameValue};
+use syn::{
+    Attribute, Data, DataStruct, Error, ExprPath, Field, Fields, Lit, Meta, MetaNameValue,
+    NestedMeta, Type,
+};
 
-#[proc_macro_derive(Partial, attributes(location))]
+/// Derive `toml_query::read::Partial`
+///
+/// The struct's location is given with `#[location = "..."]` or `#[toml_query(path = "...")]`.
+/// Fields may have a location of their own with the same attributes, which is relative to the
+/// struct's location if it starts with a '.' and absolute otherwise. Such structs are read and
+/// written field by field, fields without a location are at their name in the struct's location.
+/// Writing a field which is `None` deletes its value and the tables which become empty.
+///
+/// Of the serde attributes, such structs support `#[serde(rename = "...")]` on fields without a
+/// location, `#[serde(default)]`, `#[serde(default = "...")]` and `#[serde(skip)]`. Other serde
+/// attributes would not be honored when reading field by field, so they are rejected.
+#[proc_macro_derive(Partial, attributes(location, toml_query))]
 pub fn derive_partial(tokens: TokenStream) -> TokenStream {
     let ast: syn::DeriveInput = syn::parse(tokens).unwrap();
-    let mut location: Option<String> = None;
+    let location: Option<String> = location_of(&ast.attrs);
     let name = &ast.ident;
 
-    // Iterate over the struct's #[...] attributes
-    for option in ast.attrs.into_iter() {
-        let option = option.parse_meta().unwrap();
-        match option {
-            // Match '#[ident = lit]' attributes. Match guard makes it '#[prefix = lit]'
+    // Fields with their own location, as (field, location) pairs
+    let fields = match ast.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(ref fields),
+            ..
+        }) => fields
+            .named
+            .iter()
+            .map(|field| (field, location_of(&field.attrs)))
+            .collect(),
+        _ => vec![],
+    };
+
+    let gen = if fields.iter().all(|(_, path)| path.is_none()) {
+        let location = location.unwrap();
+
+        quote! {
+            impl<'a> ::toml_query::read::Partial<'a> for #name {
+                const LOCATION : &'static str = #location;
+                type  Output                  = Self;
+            }
+        }
+    } else {
+        if let Some(attr) = ast.attrs.iter().find(|attr| attr.path.is_ident("serde")) {
+            let msg = "serde attributes on the struct are not supported with field locations";
+            return Error::new_spanned(attr, msg).to_compile_error().into();
+        }
+
+        let serde = match fields
+            .iter()
+            .map(|(field, path)| serde_of(field, path.is_some()))
+            .collect::<Result<Vec<_>, Error>>()
+        {
+            Ok(serde) => serde,
+            Err(e) => return e.to_compile_error().into(),
+        };
+
+        // Skipped fields are neither read nor written
+        let skipped = fields
+            .iter()
+            .zip(serde.iter())
+            .filter(|(_, serde)| serde.skip)
+            .map(|((field, _), _)| field.ident.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        let (fields, serde): (Vec<_>, Vec<_>) = fields
+            .iter()
+            .zip(serde)
+            .filter(|(_, serde)| !serde.skip)
+            .unzip();
+
+        let idents = fields
+            .iter()
+            .map(|(field, _)| field.ident.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        let names = idents
+            .iter()
+            .zip(serde.iter())
+            .map(|(ident, serde)| serde.rename.clone().unwrap_or_else(|| ident.to_string()))
+            .collect::<Vec<_>>();
+        let types = fields
+            .iter()
+            .map(|(field, _)| &field.ty)
+            .collect::<Vec<_>>();
+
+        // Fields without a location of their own are at their name in the struct's location
+        let queries = fields
+            .iter()
+            .zip(names.iter())
+            .map(|((_, path), name)| match path {
+                Some(path) => field_query(location.as_deref(), path),
+                None => field_query(location.as_deref(), &format!(".{}", name)),
+            })
+            .collect::<Vec<_>>();
+
+        // Missing fields are their default if they have one or `None` if they are optional,
+        // other fields must be present
+        let getters = idents
+            .iter()
+            .zip(types.iter())
+            .zip(queries.iter())
+            .zip(serde.iter())
+            .map(|(((ident, ty), query), serde)| match serde.default {
+                Some(Some(ref path)) => quote! { #ident.unwrap_or_else(#path) },
+                Some(None) => quote! { #ident.unwrap_or_default() },
+                None if is_option(ty) => quote! { #ident.unwrap_or(None) },
+                None => {
+                    quote! { ::toml_query::read::partial_fields::required(#ident, #query)? }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let location = location.unwrap_or_default();
+
+        quote! {
+            impl<'a> ::toml_query::read::Partial<'a> for #name {
+                const LOCATION : &'static str = #location;
+                type  Output                  = Self;
+
+                fn read_from<R>(doc: &'a R) -> ::toml_query::error::Result<Option<Self>>
+                where
+                    R: ::toml_query::read::TomlValueReadExt<'a> + ?Sized,
+                {
+                    let mut found = false;
+                    #(
+                        let #idents = ::toml_query::read::partial_fields::read::<R, #types>(
+                            doc,
+                            #queries,
+                            &mut found,
+                        )?;
+                    )*
+
+                    if !found {
+                        return Ok(None);
+                    }
+
+                    Ok(Some(#name {
+                        #( #idents: #getters, )*
+                        #( #skipped: ::std::default::Default::default(), )*
+                    }))
+                }
+
+                fn read_strict_from<R>(doc: &'a R) -> ::toml_query::error::Result<Option<Self>>
+                where
+                    R: ::toml_query::read::TomlValueReadExt<'a> + ?Sized,
+                {
+                    let mut found = false;
+                    let mut unused = vec![];
+                    #(
+                        let #idents = ::toml_query::read::partial_fields::read_strict::<R, #types>(
+                            doc,
+                            #queries,
+                            &mut found,
+                            &mut unused,
+                        )?;
+                    )*
+                    ::toml_query::read::partial_fields::unclaimed(
+                        doc,
+                        #location,
+                        &[#( #queries ),*],
+                        &mut unused,
+                    )?;
+
+                    if !unused.is_empty() {
+                        return Err(::toml_query::error::Error::UnusedKeys(unused));
+                    }
+
+                    if !found {
+                        return Ok(None);
+                    }
+
+                    Ok(Some(#name {
+                        #( #idents: #getters, )*
+                        #( #skipped: ::std::default::Default::default(), )*
+                    }))
+                }
+
+                fn set_in<S>(
+                    data: &Self,
+                    doc: &mut S,
+                ) -> ::toml_query::error::Result<Option<::toml_query::read::partial_fields::Value>>
+                where
+                    S: ::toml_query::set::TomlValueSetExt
+                        + ::toml_query::delete::TomlValueDeleteExt
+                        + ?Sized,
+                {
+                    let mut old = ::toml_query::read::partial_fields::Map::new();
+                    #(
+                        ::toml_query::read::partial_fields::set(
+                            doc,
+                            #queries,
+                            #names,
+                            &data.#idents,
+                            &mut old,
+                        )?;
+                    )*
+                    Ok(::toml_query::read::partial_fields::old(old))
+                }
+
+                fn insert_in<I>(
+                    data: &Self,
+                    doc: &mut I,
+                ) -> ::toml_query::error::Result<Option<::toml_query::read::partial_fields::Value>>
+                where
+                    I: ::toml_query::insert::TomlValueInsertExt
+                        + ::toml_query::delete::TomlValueDeleteExt
+                        + ?Sized,
+                {
+                    let mut old = ::toml_query::read::partial_fields::Map::new();
+                    #(
+                        ::toml_query::read::partial_fields::insert(
+                            doc,
+                            #queries,
+                            #names,
+                            &data.#idents,
+                            &mut old,
+                        )?;
+                    )*
+                    Ok(::toml_query::read::partial_fields::old(old))
+                }
+            }
+        }
+    };
+
+    gen.into()
+}
+
+/// Find the location in `#[location = "..."]` or `#[toml_query(path = "...")]` attributes
+fn location_of(attrs: &[Attribute]) -> Option<String> {
+    let mut location = None;
+
+    // Iterate over the #[...] attributes, other attributes are not ours to parse
+    for attr in attrs {
+        if !attr.path.is_ident("location") && !attr.path.is_ident("toml_query") {
+            continue;
+        }
+
+        match attr.parse_meta().unwrap() {
+            // Match '#[location = lit]'
             Meta::NameValue(MetaNameValue {
-                ref path, ref lit, ..
-            }) if path.is_ident("location") => {
-                if let Lit::Str(lit) = lit {
-                    location = Some(lit.value());
+                lit: Lit::Str(ref lit),
+                ..
+            }) => {
+                location = Some(lit.value());
+            }
+
+            // Match '#[toml_query(path = lit)]'
+            Meta::List(ref list) => {
+                for nested in list.nested.iter() {
+                    match nested {
+                        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                            ref path,
+                            lit: Lit::Str(ref lit),
+                            ..
+                        })) if path.is_ident("path") => {
+                            location = Some(lit.value());
+                        }
+                        _ => panic!("Expected #[toml_query(path = \"...\")]"),
+                    }
                 }
             }
-            _ => {} // ...
+
+            _ => panic!("Expected #[location = \"...\"]"),
         }
     }
 
-    let location = location.unwrap();
+    location
+}
+
+/// The serde attributes of a field which are supported with field locations
+#[derive(Default)]
+struct Serde {
+    /// `#[serde(rename = "...")]`
+    rename: Option<String>,
+    /// `#[serde(default)]` as `Some(None)`, `#[serde(default = "...")]` with its function
+    default: Option<Option<ExprPath>>,
+    /// `#[serde(skip)]`
+    skip: bool,
+}
+
+/// Find the serde attributes of a field, failing for attributes which cannot be honored when
+/// reading and writing field by field
+fn serde_of(field: &Field, has_location: bool) -> Result<Serde, Error> {
+    let mut serde = Serde::default();
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+    {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            other => return Err(Error::new_spanned(other, "Expected #[serde(...)]")),
+        };
+
+        for nested in list.nested.iter() {
+            match nested {
+                // Match 'rename = lit', the location names the field if it has one
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref path,
+                    lit: Lit::Str(ref lit),
+                    ..
+                })) if path.is_ident("rename") && !has_location => {
+                    serde.rename = Some(lit.value());
+                }
+
+                // Match 'default = lit'
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref path,
+                    lit: Lit::Str(ref lit),
+                    ..
+                })) if path.is_ident("default") => {
+                    serde.default = Some(Some(lit.parse()?));
+                }
+
+                // Match 'default'
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("default") => {
+                    serde.default = Some(None);
+                }
+
+                // Match 'skip'
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("skip") => {
+                    serde.skip = true;
+                }
+
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { ref path, .. }))
+                    if path.is_ident("rename") && has_location =>
+                {
+                    let msg = "#[serde(rename)] conflicts with the location of the field";
+                    return Err(Error::new_spanned(nested, msg));
+                }
 
-    let gen = quote! {
-        impl<'a> ::toml_query::read::Partial<'a> for #name {
-            const LOCATION : &'static str = #location;
-            type  Output                  = Self;
+                _ => {
+                    let msg = "This serde attribute is not supported with field locations";
+                    return Err(Error::new_spanned(nested, msg));
+                }
+            }
         }
-    };
+    }
 
-    gen.into()
+    Ok(serde)
+}
+
+/// The query of a field location, which is relative to the struct's location if it starts with
+/// a '.' and absolute otherwise
+fn field_query(location: Option<&str>, path: &str) -> String {
+    match (path.strip_prefix('.'), location) {
+        (Some(relative), Some(location)) => format!("{}.{}", location, relative),
+        (Some(relative), None) => relative.to_owned(),
+        (None, _) => path.to_owned(),
+    }
+}
+
+/// Whether the type of a field is an `Option`, which may be missing in the document
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(ref ty) if ty.qself.is_none() => {
+            matches!(ty.path.segments.last(), Some(segment) if segment.ident == "Option")
+        }
+        _ => false,
+    }
 }